@@ -0,0 +1,121 @@
+#[macro_use]
+extern crate criterion;
+extern crate html5ever;
+
+use std::fs;
+use std::iter;
+use std::path::PathBuf;
+
+use criterion::{black_box, Benchmark, Criterion, Throughput};
+
+use html5ever::rcdom::{Handle, RcDom};
+use html5ever::serialize::{
+    serialize, serialize_to_string_exact, serialize_to_string_with_capacity, SerializeOpts,
+};
+use html5ever::tendril::*;
+use html5ever::{parse_document, ParseOpts};
+
+fn run_bench(
+    c: &mut Criterion,
+    name: &str,
+    to_string: fn(&Handle, SerializeOpts, usize) -> String,
+) {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("data/bench/lipsum.html");
+    let input = fs::read_to_string(&path).expect("can't read file");
+
+    // Make a large document out of many copies of the file.
+    let input = input.repeat(50);
+    let size = input.len();
+    let dom = parse_document(RcDom::default(), ParseOpts::default()).one(input);
+
+    c.bench_function(name, move |b| {
+        b.iter(|| {
+            black_box(to_string(
+                &dom.document,
+                SerializeOpts::spec_compliant(),
+                size,
+            ))
+        })
+    });
+}
+
+fn naive(document: &Handle, opts: SerializeOpts, _size: usize) -> String {
+    let mut output = vec![];
+    serialize(&mut output, document, opts).unwrap();
+    String::from_utf8(output).unwrap()
+}
+
+fn with_capacity(document: &Handle, opts: SerializeOpts, size: usize) -> String {
+    serialize_to_string_with_capacity(document, opts, size).unwrap()
+}
+
+fn exact(document: &Handle, opts: SerializeOpts, _size: usize) -> String {
+    serialize_to_string_exact(document, opts).unwrap()
+}
+
+fn serializer_benchmark(c: &mut Criterion) {
+    run_bench(c, "serialize to String", naive);
+    run_bench(c, "serialize to String with capacity", with_capacity);
+    run_bench(c, "serialize to String exact", exact);
+}
+
+/// Measure the throughput of serializing `input`, in bytes of output.
+fn run_throughput_bench(c: &mut Criterion, name: &str, input: String) {
+    let dom = parse_document(RcDom::default(), ParseOpts::default()).one(input);
+    let size = serialize_to_string_exact(&dom.document, SerializeOpts::spec_compliant())
+        .unwrap()
+        .len();
+    c.bench(
+        "serialize",
+        Benchmark::new(name, move |b| {
+            b.iter(|| {
+                let mut output = Vec::with_capacity(size);
+                serialize(&mut output, &dom.document, SerializeOpts::spec_compliant()).unwrap();
+                black_box(output)
+            })
+        })
+        .throughput(Throughput::Bytes(size as u32)),
+    );
+}
+
+fn prose() -> String {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("data/bench/lipsum.html");
+    fs::read_to_string(&path)
+        .expect("can't read file")
+        .repeat(50)
+}
+
+fn attribute_heavy() -> String {
+    let link = "<a href=\"https://example.com/search?q=caf\u{e9}&amp;page=2\" \
+                title=\"Say &quot;hi&quot; &amp; leave\" class=\"nav-link active\" \
+                data-id=\"12345\" aria-label=\"Next &lt;page&gt;\">x</a>";
+    iter::repeat(link).take(5000).collect()
+}
+
+fn large_attribute_values() -> String {
+    let value = "a &amp; &quot;b&quot; ".repeat(4000);
+    let element = format!("<div title=\"{}\" data-value=\"{}\"></div>", value, value);
+    element.repeat(20)
+}
+
+fn deeply_nested() -> String {
+    let depth = 200;
+    let nested = format!(
+        "{}<span>text &amp; more</span>{}",
+        "<div class=\"level\">".repeat(depth),
+        "</div>".repeat(depth)
+    );
+    nested.repeat(50)
+}
+
+fn throughput_benchmark(c: &mut Criterion) {
+    run_throughput_bench(c, "prose", prose());
+    run_throughput_bench(c, "attribute-heavy", attribute_heavy());
+    run_throughput_bench(c, "large attribute values", large_attribute_values());
+    run_throughput_bench(c, "deeply nested", deeply_nested());
+}
+
+criterion_group!(benches, serializer_benchmark, throughput_benchmark);
+criterion_main!(benches);
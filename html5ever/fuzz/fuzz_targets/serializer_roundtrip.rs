@@ -0,0 +1,103 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parses the input, serializes the tree, parses the output again and checks
+//! that both parses give the same tree. A difference means the serializer wrote
+//! something that the parser reads differently, which is how escaping bugs
+//! turn into injection.
+
+#![no_main]
+extern crate html5ever;
+#[macro_use]
+extern crate libfuzzer_sys;
+
+use std::str;
+
+use html5ever::rcdom::{Handle, NodeData, RcDom};
+use html5ever::serialize::{serialize, SerializeOpts};
+use html5ever::tendril::TendrilSink;
+use html5ever::{parse_document, ParseOpts};
+
+fn parse(input: &str) -> RcDom {
+    parse_document(RcDom::default(), ParseOpts::default()).one(input)
+}
+
+/// Describes the first difference between the trees under `a` and `b`.
+fn compare(a: &Handle, b: &Handle, path: &str) -> Result<(), String> {
+    match (&a.data, &b.data) {
+        (&NodeData::Document, &NodeData::Document) => (),
+        // Public and system identifiers aren't serialized.
+        (&NodeData::Doctype { name: ref x, .. }, &NodeData::Doctype { name: ref y, .. })
+            if x == y => {},
+        (&NodeData::Text { contents: ref x }, &NodeData::Text { contents: ref y })
+            if *x.borrow() == *y.borrow() => {},
+        (&NodeData::Comment { contents: ref x }, &NodeData::Comment { contents: ref y })
+            if x == y => {},
+        (
+            &NodeData::ProcessingInstruction {
+                target: ref t1,
+                contents: ref c1,
+            },
+            &NodeData::ProcessingInstruction {
+                target: ref t2,
+                contents: ref c2,
+            },
+        ) if t1 == t2 && c1 == c2 => {},
+        (
+            &NodeData::Element {
+                name: ref n1,
+                attrs: ref a1,
+                template_contents: ref t1,
+                ..
+            },
+            &NodeData::Element {
+                name: ref n2,
+                attrs: ref a2,
+                template_contents: ref t2,
+                ..
+            },
+        ) if n1 == n2 && *a1.borrow() == *a2.borrow() => match (t1, t2) {
+            (&Some(ref t1), &Some(ref t2)) => {
+                try!(compare(t1, t2, &format!("{}/#template", path)))
+            },
+            (&None, &None) => (),
+            _ => return Err(format!("{}: template contents differ", path)),
+        },
+        _ => return Err(format!("{}: nodes differ", path)),
+    }
+
+    let (a, b) = (a.children.borrow(), b.children.borrow());
+    for (i, (x, y)) in a.iter().zip(b.iter()).enumerate() {
+        try!(compare(x, y, &format!("{}/{}", path, i)));
+    }
+    if a.len() != b.len() {
+        return Err(format!("{}: {} children became {}", path, a.len(), b.len()));
+    }
+    Ok(())
+}
+
+fuzz_target!(|data: &[u8]| {
+    let input = match str::from_utf8(data) {
+        Ok(input) => input,
+        Err(_) => return,
+    };
+    let first = parse(input);
+
+    let mut output = vec![];
+    serialize(&mut output, &first.document, SerializeOpts::spec_compliant()).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    let second = parse(&output);
+
+    if let Err(difference) = compare(&first.document, &second.document, "") {
+        panic!(
+            "serializing changed the tree\n{}\ninput: {:?}\noutput: {:?}",
+            difference, input, output
+        );
+    }
+});
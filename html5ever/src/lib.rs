@@ -18,13 +18,17 @@ extern crate log;
 extern crate markup5ever;
 #[macro_use]
 extern crate mac;
-#[macro_use]
-extern crate lazy_static;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "unicode-normalization")]
+extern crate unicode_normalization;
 
 pub use driver::{parse_document, parse_fragment, ParseOpts, Parser};
 pub use markup5ever::*;
 
-pub use serialize::serialize;
+pub use serialize::{sanitize_html, serialize};
 
 #[macro_use]
 mod macros;
@@ -0,0 +1,113 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serialization to a `tokio::io::AsyncWrite`. Requires the `tokio` feature.
+
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tokio::io::AsyncWrite;
+
+use super::{HtmlSerializer, SerializeOpts};
+use rcdom::{Handle, SerializeSteps};
+
+/// How much output is serialized ahead of what the writer has taken. A single
+/// node, such as a long text, can go over it.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+/// Serialize `node` to an asynchronous writer, a little at a time.
+///
+/// The markup is produced by the same `HtmlSerializer` as [`serialize`], so the
+/// output is byte-for-byte identical. The returned future serializes nodes
+/// until about 8 KiB of output is waiting, then hands that to `writer` before
+/// serializing more, so a large document is never held in memory as a whole,
+/// and the executor gets control back whenever `writer` isn't ready.
+///
+/// This walks the tree one node at a time, which only an `RcDom` `Handle`
+/// supports. Since it holds `Rc`s, the future isn't `Send`.
+///
+/// [`serialize`]: fn.serialize.html
+pub fn serialize_async<Wr>(writer: Wr, node: &Handle, opts: SerializeOpts) -> SerializeAsync<Wr>
+where
+    Wr: AsyncWrite + Unpin,
+{
+    SerializeAsync {
+        writer: writer,
+        steps: SerializeSteps::new(node, opts.traversal_scope.clone()),
+        ser: HtmlSerializer::new(Vec::with_capacity(BUFFER_SIZE), opts),
+        pos: 0,
+        done: false,
+    }
+}
+
+/// Future returned by [`serialize_async`].
+///
+/// [`serialize_async`]: fn.serialize_async.html
+pub struct SerializeAsync<Wr> {
+    writer: Wr,
+    steps: SerializeSteps,
+    /// Serializes into a buffer that is emptied whenever the writer has taken
+    /// all of it.
+    ser: HtmlSerializer<Vec<u8>>,
+    /// How much of the buffer the writer has taken.
+    pos: usize,
+    /// Whether the traversal is complete and the serializer finished.
+    done: bool,
+}
+
+impl<Wr> SerializeAsync<Wr> {
+    /// Recover the writer, e.g. after the future has completed.
+    pub fn into_inner(self) -> Wr {
+        self.writer
+    }
+}
+
+impl<Wr: AsyncWrite + Unpin> Future for SerializeAsync<Wr> {
+    type Output = io::Result<()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            while this.pos < this.ser.writer.len() {
+                match Pin::new(&mut this.writer).poll_write(cx, &this.ser.writer[this.pos..]) {
+                    Poll::Ready(Ok(0)) => {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::WriteZero,
+                            "failed to write whole buffer",
+                        )));
+                    },
+                    Poll::Ready(Ok(n)) => this.pos += n,
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            this.ser.writer.clear();
+            this.pos = 0;
+
+            if this.done {
+                return Pin::new(&mut this.writer).poll_flush(cx);
+            }
+            while this.ser.writer.len() < BUFFER_SIZE {
+                match this.steps.step(&mut this.ser) {
+                    Ok(true) => (),
+                    Ok(false) => {
+                        if let Err(e) = this.ser.finish() {
+                            return Poll::Ready(Err(e));
+                        }
+                        this.done = true;
+                        break;
+                    },
+                    Err(e) => return Poll::Ready(Err(e)),
+                }
+            }
+        }
+    }
+}
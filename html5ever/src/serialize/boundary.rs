@@ -0,0 +1,137 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, Write};
+
+use super::writers::HashingWriter;
+use super::{AttrRef, EntitySpan, HtmlSerializer, Serialize, SerializeOpts, Serializer};
+use interface::QuirksMode;
+use QualName;
+
+/// Serialize `node` like [`serialize`], and call `on_boundary` after each start
+/// and end tag with a hash of the output written since the previous call. The
+/// output is split into segments at element boundaries, so two serializations
+/// can be compared segment by segment without keeping either one around.
+/// Output after the last tag is reported as a final segment.
+///
+/// The hash is 64-bit FNV-1a, which is stable across platforms and releases,
+/// but isn't cryptographic.
+///
+/// [`serialize`]: fn.serialize.html
+pub fn serialize_with_boundaries<Wr, T, F>(
+    writer: Wr,
+    node: &T,
+    opts: SerializeOpts,
+    on_boundary: F,
+) -> io::Result<()>
+where
+    Wr: Write,
+    T: Serialize,
+    F: FnMut(u64),
+{
+    let mut ser = BoundaryHashSerializer::new(writer, opts.clone(), on_boundary);
+    try!(node.serialize(&mut ser, opts.traversal_scope));
+    ser.finish()
+}
+
+/// An `HtmlSerializer` that reports a hash of its output at every element
+/// boundary. See [`serialize_with_boundaries`].
+///
+/// [`serialize_with_boundaries`]: fn.serialize_with_boundaries.html
+pub struct BoundaryHashSerializer<Wr: Write, F: FnMut(u64)> {
+    inner: HtmlSerializer<HashingWriter<Wr>>,
+    on_boundary: F,
+}
+
+impl<Wr: Write, F: FnMut(u64)> BoundaryHashSerializer<Wr, F> {
+    /// Write to `writer`, calling `on_boundary` with the hash of each segment.
+    pub fn new(writer: Wr, opts: SerializeOpts, on_boundary: F) -> Self {
+        BoundaryHashSerializer {
+            inner: HtmlSerializer::new(HashingWriter::new(writer), opts),
+            on_boundary: on_boundary,
+        }
+    }
+
+    /// Finish serializing, as `HtmlSerializer::finish` does, and report the
+    /// output written since the last boundary, if there is any.
+    pub fn finish(&mut self) -> io::Result<()> {
+        try!(self.inner.finish());
+        if self.inner.writer.segment_len() > 0 {
+            self.boundary();
+        }
+        Ok(())
+    }
+
+    /// Unwrap the underlying writer.
+    pub fn into_inner(self) -> Wr {
+        self.inner.into_inner().into_inner()
+    }
+
+    fn boundary(&mut self) {
+        let hash = self.inner.writer.take_hash();
+        (self.on_boundary)(hash);
+    }
+}
+
+impl<Wr: Write, F: FnMut(u64)> Serializer for BoundaryHashSerializer<Wr, F> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.start_elem_as_written(name, attrs, false)
+    }
+
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        try!(self.inner.start_elem_as_written(name, attrs, self_closing));
+        self.boundary();
+        Ok(())
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        try!(self.inner.end_elem(name));
+        self.boundary();
+        Ok(())
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.inner.write_text(text)
+    }
+
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        self.inner.write_text_with_entities(text, entities)
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.inner.write_comment(text)
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.inner.write_doctype(name)
+    }
+
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        self.inner.write_doctype_raw(raw)
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.inner.write_processing_instruction(target, data)
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.inner.set_quirks_mode(mode)
+    }
+}
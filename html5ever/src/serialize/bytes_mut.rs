@@ -0,0 +1,31 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Serialization into a `bytes::BytesMut`. Requires the `bytes` feature.
+
+use std::io;
+
+use bytes::{BufMut, BytesMut};
+
+use super::{serialize, Serialize, SerializeOpts};
+
+/// Serialize `node` like [`serialize`], appending the output to `buf`.
+///
+/// The markup is written straight into the buffer, which grows as needed, so a
+/// buffer can be reused across responses and split off with
+/// `BytesMut::split().freeze()` without copying.
+///
+/// [`serialize`]: fn.serialize.html
+pub fn serialize_into_bytes<T: Serialize>(
+    buf: &mut BytesMut,
+    node: &T,
+    opts: SerializeOpts,
+) -> io::Result<()> {
+    serialize(buf.writer(), node, opts)
+}
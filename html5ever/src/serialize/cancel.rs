@@ -0,0 +1,135 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::{AttrRef, EntitySpan, HtmlSerializer, Serialize, SerializeOpts, Serializer};
+use interface::QuirksMode;
+use QualName;
+
+/// Serialize `node` like [`serialize`], giving up with a [`Cancelled`] error at
+/// the next element once `should_cancel` is set, e.g. from another thread when
+/// the client the output is for has gone away.
+///
+/// [`serialize`]: fn.serialize.html
+/// [`Cancelled`]: struct.Cancelled.html
+pub fn serialize_cancellable<Wr, T>(
+    writer: Wr,
+    node: &T,
+    opts: SerializeOpts,
+    should_cancel: &AtomicBool,
+) -> io::Result<()>
+where
+    Wr: Write,
+    T: Serialize,
+{
+    let mut ser =
+        CancellableSerializer::new(HtmlSerializer::new(writer, opts.clone()), should_cancel);
+    try!(node.serialize(&mut ser, opts.traversal_scope));
+    ser.into_inner().finish()
+}
+
+/// The error, wrapped in an `io::Error` of kind `Other`, that a
+/// `CancellableSerializer` fails with once it has been cancelled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("serialization was cancelled")
+    }
+}
+
+impl Error for Cancelled {
+    fn description(&self) -> &str {
+        "serialization was cancelled"
+    }
+}
+
+/// A `Serializer` that passes everything on to another one until a flag is set.
+/// The flag is checked at the start of each element, which is cheap enough to do
+/// that often and frequent enough that little output is written after it is set.
+pub struct CancellableSerializer<'c, S> {
+    inner: S,
+    should_cancel: &'c AtomicBool,
+}
+
+impl<'c, S: Serializer> CancellableSerializer<'c, S> {
+    /// Pass everything on to `inner` until `should_cancel` is set.
+    pub fn new(inner: S, should_cancel: &'c AtomicBool) -> Self {
+        CancellableSerializer {
+            inner: inner,
+            should_cancel: should_cancel,
+        }
+    }
+
+    /// Unwrap the inner serializer.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<'c, S: Serializer> Serializer for CancellableSerializer<'c, S> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.start_elem_as_written(name, attrs, false)
+    }
+
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        if self.should_cancel.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Other, Cancelled));
+        }
+        self.inner.start_elem_as_written(name, attrs, self_closing)
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        self.inner.end_elem(name)
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.inner.write_text(text)
+    }
+
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        self.inner.write_text_with_entities(text, entities)
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.inner.write_comment(text)
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.inner.write_doctype(name)
+    }
+
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        self.inner.write_doctype_raw(raw)
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.inner.write_processing_instruction(target, data)
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.inner.set_quirks_mode(mode)
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::sync::Arc;
+
+use QualName;
+
+/// A function called with the name and attributes of each element before it is
+/// written, for `SerializeOpts::element_hook`. It can change, add or remove
+/// attributes, e.g. to add the `style` computed by a CSS inliner for email.
+#[derive(Clone)]
+pub struct ElementHook(Arc<dyn Fn(&QualName, &mut Vec<(QualName, String)>) + Send + Sync>);
+
+impl ElementHook {
+    /// Call `hook` for each element.
+    pub fn new<F>(hook: F) -> Self
+    where
+        F: Fn(&QualName, &mut Vec<(QualName, String)>) + Send + Sync + 'static,
+    {
+        ElementHook(Arc::new(hook))
+    }
+
+    /// Call the hook for an element named `name` with `attrs`.
+    pub fn call(&self, name: &QualName, attrs: &mut Vec<(QualName, String)>) {
+        (self.0)(name, attrs)
+    }
+}
+
+impl fmt::Debug for ElementHook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ElementHook(..)")
+    }
+}
+
+/// Two hooks are equal if they are clones of each other.
+impl PartialEq for ElementHook {
+    fn eq(&self, other: &ElementHook) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
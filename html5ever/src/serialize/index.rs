@@ -0,0 +1,91 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io;
+
+use super::{AttrRef, Serialize, Serializer, TraversalScope};
+use {LocalName, QualName};
+
+/// The text nodes inside `node`, each with the path of elements it is in, for
+/// a full-text search index. See [`TextIndexSerializer`].
+///
+/// [`TextIndexSerializer`]: struct.TextIndexSerializer.html
+pub fn serialize_text_index<T: Serialize>(node: &T) -> Vec<(String, String)> {
+    let mut ser = TextIndexSerializer::new();
+    node.serialize(&mut ser, TraversalScope::ChildrenOnly(None))
+        .expect("TextIndexSerializer never fails");
+    ser.into_records()
+}
+
+/// A `Serializer` that, instead of HTML, collects a `(path, text)` record for
+/// each text node it is given, where the path is the local names of the
+/// elements the text is in, outermost first, joined by `>`, as in
+/// `html>body>p`. Text that is only whitespace is left out, and so is
+/// everything other than text.
+pub struct TextIndexSerializer {
+    /// The open elements, innermost last.
+    stack: Vec<LocalName>,
+    records: Vec<(String, String)>,
+}
+
+impl TextIndexSerializer {
+    /// A serializer with no records.
+    pub fn new() -> Self {
+        TextIndexSerializer {
+            stack: vec![],
+            records: vec![],
+        }
+    }
+
+    /// The records collected so far, in document order.
+    pub fn into_records(self) -> Vec<(String, String)> {
+        self.records
+    }
+
+    fn path(&self) -> String {
+        let names: Vec<&str> = self.stack.iter().map(|name| &**name).collect();
+        names.join(">")
+    }
+}
+
+impl Serializer for TextIndexSerializer {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, _attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.stack.push(name.local);
+        Ok(())
+    }
+
+    fn end_elem(&mut self, _name: QualName) -> io::Result<()> {
+        self.stack.pop();
+        Ok(())
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        if text.chars().all(char::is_whitespace) {
+            return Ok(());
+        }
+        let path = self.path();
+        self.records.push((path, text.to_owned()));
+        Ok(())
+    }
+
+    fn write_comment(&mut self, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_doctype(&mut self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_processing_instruction(&mut self, _target: &str, _data: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A `Serializer` that writes the traversal as indented JSON instead of HTML.
+//!
+//! The output is an array of nodes. Elements look like
+//!
+//! ```text
+//! {
+//!   "type": "element",
+//!   "name": "p",
+//!   "attributes": {
+//!     "class": "x"
+//!   },
+//!   "children": [ ... ]
+//! }
+//! ```
+//!
+//! and elements outside the HTML namespace also have a `"namespace"` field.
+//! Text and comments are `{"type": "text", "value": ...}` and
+//! `{"type": "comment", "value": ...}`; doctypes and processing instructions
+//! carry `"name"`, and `"target"` and `"data"` respectively.
+
+use std::io::{self, Write};
+
+use super::{AttrRef, Serialize, Serializer, TraversalScope};
+use QualName;
+
+/// Write `node` as JSON. See the [module documentation](index.html) for the format.
+pub fn serialize_json<Wr, T>(
+    writer: Wr,
+    node: &T,
+    traversal_scope: TraversalScope,
+) -> io::Result<()>
+where
+    Wr: Write,
+    T: Serialize,
+{
+    let mut ser = JsonSerializer::new(writer);
+    try!(node.serialize(&mut ser, traversal_scope));
+    ser.finish()
+}
+
+pub struct JsonSerializer<Wr: Write> {
+    pub writer: Wr,
+    /// One entry per open array of nodes, the outermost one first: whether
+    /// anything has been written to it yet.
+    stack: Vec<bool>,
+}
+
+fn write_json_str<Wr: Write>(writer: &mut Wr, s: &str) -> io::Result<()> {
+    try!(writer.write_all(b"\""));
+    for c in s.chars() {
+        try!(match c {
+            '"' => writer.write_all(b"\\\""),
+            '\\' => writer.write_all(b"\\\\"),
+            '\n' => writer.write_all(b"\\n"),
+            '\r' => writer.write_all(b"\\r"),
+            '\t' => writer.write_all(b"\\t"),
+            c if (c as u32) < 0x20 => write!(writer, "\\u{:04x}", c as u32),
+            c => writer.write_all(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        });
+    }
+    writer.write_all(b"\"")
+}
+
+impl<Wr: Write> JsonSerializer<Wr> {
+    /// Write the JSON to `writer`.
+    pub fn new(writer: Wr) -> Self {
+        JsonSerializer {
+            writer: writer,
+            stack: vec![false],
+        }
+    }
+
+    /// Close the outermost array. Call this once the traversal is complete.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.stack.len() > 1 {
+            warn!("JsonSerializer finished with unclosed elements");
+        }
+        if self.stack[0] {
+            self.writer.write_all(b"\n]")
+        } else {
+            self.writer.write_all(b"[]")
+        }
+    }
+
+    /// Indentation level of the nodes in the innermost open array.
+    fn depth(&self) -> usize {
+        2 * self.stack.len() - 1
+    }
+
+    fn write_indent(&mut self, depth: usize) -> io::Result<()> {
+        for _ in 0..depth {
+            try!(self.writer.write_all(b"  "));
+        }
+        Ok(())
+    }
+
+    /// Write the separator before a new node in the innermost array.
+    fn begin_node(&mut self) -> io::Result<()> {
+        let outermost = self.stack.len() == 1;
+        let started = self.stack.last_mut().unwrap();
+        if *started {
+            try!(self.writer.write_all(b","));
+        } else if outermost {
+            try!(self.writer.write_all(b"["));
+        }
+        *started = true;
+        try!(self.writer.write_all(b"\n"));
+        let depth = self.depth();
+        self.write_indent(depth)
+    }
+
+    fn write_field(&mut self, key: &str, value: &str) -> io::Result<()> {
+        let depth = self.depth() + 1;
+        try!(self.write_indent(depth));
+        try!(write_json_str(&mut self.writer, key));
+        try!(self.writer.write_all(b": "));
+        write_json_str(&mut self.writer, value)
+    }
+
+    fn write_leaf(&mut self, fields: &[(&str, &str)]) -> io::Result<()> {
+        try!(self.begin_node());
+        try!(self.writer.write_all(b"{"));
+        for (i, &(key, value)) in fields.iter().enumerate() {
+            try!(self.writer.write_all(if i == 0 { b"\n" } else { b",\n" }));
+            try!(self.write_field(key, value));
+        }
+        try!(self.writer.write_all(b"\n"));
+        let depth = self.depth();
+        try!(self.write_indent(depth));
+        self.writer.write_all(b"}")
+    }
+}
+
+fn attr_name(name: &QualName) -> String {
+    match name.prefix {
+        Some(ref prefix) => format!("{}:{}", prefix, name.local),
+        None => name.local.to_string(),
+    }
+}
+
+impl<Wr: Write> Serializer for JsonSerializer<Wr> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        try!(self.begin_node());
+        let depth = self.depth();
+        try!(self.writer.write_all(b"{\n"));
+        try!(self.write_field("type", "element"));
+        try!(self.writer.write_all(b",\n"));
+        try!(self.write_field("name", &name.local));
+        try!(self.writer.write_all(b",\n"));
+        if name.ns != ns!(html) {
+            try!(self.write_field("namespace", &name.ns));
+            try!(self.writer.write_all(b",\n"));
+        }
+
+        try!(self.write_indent(depth + 1));
+        try!(self.writer.write_all(b"\"attributes\": {"));
+        let mut any_attrs = false;
+        for (attr, value) in attrs {
+            try!(self
+                .writer
+                .write_all(if any_attrs { b",\n" } else { b"\n" }));
+            any_attrs = true;
+            try!(self.write_indent(depth + 2));
+            try!(write_json_str(&mut self.writer, &attr_name(attr)));
+            try!(self.writer.write_all(b": "));
+            try!(write_json_str(&mut self.writer, value));
+        }
+        if any_attrs {
+            try!(self.writer.write_all(b"\n"));
+            try!(self.write_indent(depth + 1));
+        }
+        try!(self.writer.write_all(b"},\n"));
+
+        try!(self.write_indent(depth + 1));
+        try!(self.writer.write_all(b"\"children\": ["));
+        self.stack.push(false);
+        Ok(())
+    }
+
+    fn end_elem(&mut self, _name: QualName) -> io::Result<()> {
+        if self.stack.len() == 1 {
+            warn!("end_elem without a matching start_elem");
+            return Ok(());
+        }
+        let had_children = self.stack.pop().unwrap();
+        let depth = self.depth();
+        if had_children {
+            try!(self.writer.write_all(b"\n"));
+            try!(self.write_indent(depth + 1));
+        }
+        try!(self.writer.write_all(b"]\n"));
+        try!(self.write_indent(depth));
+        self.writer.write_all(b"}")
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_leaf(&[("type", "text"), ("value", text)])
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.write_leaf(&[("type", "comment"), ("value", text)])
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.write_leaf(&[("type", "doctype"), ("name", name)])
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.write_leaf(&[
+            ("type", "processing-instruction"),
+            ("target", target),
+            ("data", data),
+        ])
+    }
+}
@@ -0,0 +1,476 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A `Serializer` that writes Markdown instead of HTML.
+//!
+//! Headings, paragraphs, lists, links, `<strong>`/`<b>`, `<em>`/`<i>`, `<code>`,
+//! `<pre>`, `<blockquote>` and `<br>` are translated. Other elements are left
+//! out but their text is kept, with a paragraph break around block-level ones.
+//! Text is escaped so that it can't be read as Markdown or HTML markup, such as
+//! `<img>` tags or a `#` that would start a heading. Links are only kept if
+//! they are relative or use `http`, `https` or `mailto`.
+//! The contents of `<script>`, `<style>`, `<template>` and `<title>` are dropped,
+//! as are comments, doctypes and processing instructions.
+
+use std::io;
+
+use super::text::is_block_element;
+use super::{url_scheme, AttrRef, Serialize, Serializer, TraversalScope, SAFE_URL_SCHEMES};
+use QualName;
+
+/// The children of `node` as Markdown.
+pub fn serialize_markdown<T: Serialize>(node: &T) -> String {
+    let mut ser = MarkdownSerializer::new();
+    node.serialize(&mut ser, TraversalScope::ChildrenOnly(None))
+        .expect("MarkdownSerializer never fails");
+    ser.into_string()
+}
+
+enum Open {
+    /// A list, and the number of its next item if it is ordered.
+    List(Option<u32>),
+    /// A list item, and the width of its marker, which its later lines are
+    /// indented by.
+    Item(usize),
+    Blockquote,
+    /// A preformatted block, and its text so far, which is only written at its
+    /// end, once the fence it needs is known.
+    Pre(String),
+    /// A code span, and its text so far, like `Pre`.
+    Code(String),
+    Strong,
+    Em,
+    Link(String),
+    /// An element whose text is dropped.
+    Ignored,
+    Block,
+    Other,
+}
+
+/// A `Serializer` that collects the nodes it is given as Markdown. See the
+/// [module documentation](index.html) for what is translated.
+pub struct MarkdownSerializer {
+    markdown: String,
+    stack: Vec<Open>,
+    /// Whether nothing has been written on the current line yet, not even the
+    /// prefix for the open block quotes and list items.
+    at_line_start: bool,
+    /// A line break to write before the next content.
+    pending_newline: bool,
+    /// A blank line to write before the next content, with the prefix it should
+    /// have.
+    pending_blank_line: Option<String>,
+    /// Collapsed whitespace to write as a space before the next text.
+    pending_space: bool,
+    /// Whether a list item or heading marker was just written, so that a block
+    /// starting inside it stays on the same line.
+    after_marker: bool,
+}
+
+impl Default for MarkdownSerializer {
+    fn default() -> Self {
+        MarkdownSerializer::new()
+    }
+}
+
+impl MarkdownSerializer {
+    /// An empty document.
+    pub fn new() -> Self {
+        MarkdownSerializer {
+            markdown: String::new(),
+            stack: vec![],
+            at_line_start: true,
+            pending_newline: false,
+            pending_blank_line: None,
+            pending_space: false,
+            after_marker: false,
+        }
+    }
+
+    /// The Markdown collected so far.
+    pub fn into_string(self) -> String {
+        self.markdown
+    }
+
+    /// What each line inside the open block quotes and list items starts with.
+    fn prefix(&self) -> String {
+        let mut prefix = String::new();
+        for open in self.stack.iter() {
+            match *open {
+                Open::Blockquote => prefix.push_str("> "),
+                Open::Item(width) => prefix.push_str(&" ".repeat(width)),
+                _ => (),
+            }
+        }
+        prefix
+    }
+
+    fn line_break(&mut self) {
+        if !self.after_marker {
+            self.pending_newline = true;
+            self.pending_space = false;
+        }
+    }
+
+    /// A block may start or end inside a block quote that the paragraph before
+    /// or after it is outside of; the blank line between them only gets the
+    /// prefix of the quotes around both.
+    fn paragraph_break(&mut self) {
+        if self.after_marker {
+            return;
+        }
+        let blank_line = self.prefix().trim_end().to_owned();
+        let shorter = self
+            .pending_blank_line
+            .as_ref()
+            .map_or(true, |pending| blank_line.len() < pending.len());
+        if shorter {
+            self.pending_blank_line = Some(blank_line);
+        }
+        self.pending_space = false;
+    }
+
+    /// Write the pending breaks and the line prefix, before `markup`.
+    fn write_markup(&mut self, markup: &str) {
+        let blank_line = self.pending_blank_line.take();
+        if (self.pending_newline || blank_line.is_some()) && !self.markdown.is_empty() {
+            self.markdown.push('\n');
+            if let Some(blank_line) = blank_line {
+                self.markdown.push_str(&blank_line);
+                self.markdown.push('\n');
+            }
+            self.at_line_start = true;
+        }
+        self.pending_newline = false;
+        if self.at_line_start {
+            let prefix = self.prefix();
+            self.markdown.push_str(&prefix);
+            self.at_line_start = false;
+        }
+        if self.pending_space {
+            self.markdown.push(' ');
+            self.pending_space = false;
+        }
+        self.markdown.push_str(markup);
+        self.after_marker = false;
+    }
+
+    fn is_open<F: Fn(&Open) -> bool>(&self, predicate: F) -> bool {
+        self.stack.iter().any(predicate)
+    }
+
+    fn in_item(&self) -> bool {
+        self.is_open(|open| match *open {
+            Open::Item(_) => true,
+            _ => false,
+        })
+    }
+
+    fn write_preformatted(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.pending_newline = true;
+            }
+            self.write_markup(line);
+        }
+    }
+
+    /// The text of the open code span or preformatted block, if any.
+    fn code_text(&mut self) -> Option<&mut String> {
+        self.stack
+            .iter_mut()
+            .rev()
+            .filter_map(|open| match *open {
+                Open::Pre(ref mut text) | Open::Code(ref mut text) => Some(text),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// Write a fenced code block with a fence of at least three backticks, and
+    /// longer than any run of them in `code`.
+    fn write_code_block(&mut self, code: &str) {
+        let fence = "`".repeat(::std::cmp::max(3, longest_backtick_run(code) + 1));
+        self.write_markup(&fence);
+        self.pending_newline = true;
+        self.write_preformatted(code);
+        self.pending_newline = true;
+        self.write_markup(&fence);
+    }
+
+    /// Write a code span in a fence of backticks longer than any run of them in
+    /// `code`, so that none of them ends the span early.
+    fn write_code_span(&mut self, code: &str) {
+        let code: Vec<&str> = code
+            .split(|c: char| c.is_ascii_whitespace())
+            .filter(|word| !word.is_empty())
+            .collect();
+        let code = code.join(" ");
+        if code.is_empty() {
+            return;
+        }
+        let fence = "`".repeat(longest_backtick_run(&code) + 1);
+        // A parser strips one space from each end, which keeps backticks at
+        // the ends from running into the fence.
+        let padding = if code.starts_with('`') || code.ends_with('`') {
+            " "
+        } else {
+            ""
+        };
+        let span = format!("{}{}{}{}{}", fence, padding, code, padding, fence);
+        self.write_markup(&span);
+    }
+
+    /// Whether the next content starts a line, or the content of a list item or
+    /// heading, where text such as `# ` or `1. ` would be read as a marker.
+    fn at_block_start(&self) -> bool {
+        self.markdown.is_empty()
+            || self.at_line_start
+            || self.after_marker
+            || self.pending_newline
+            || self.pending_blank_line.is_some()
+    }
+
+    fn write_inline(&mut self, text: &str) {
+        for word in text.split(|c: char| c.is_ascii_whitespace()) {
+            if word.is_empty() {
+                // Whitespace at the start of a line is insignificant.
+                let line_start = self.markdown.is_empty()
+                    || self.after_marker
+                    || self.pending_newline
+                    || self.pending_blank_line.is_some();
+                self.pending_space = !line_start;
+                continue;
+            }
+            let block_start = self.at_block_start() && !self.pending_space;
+            let mut escaped = String::with_capacity(word.len());
+            // The `.` or `)` of an ordered list marker such as `1.`.
+            let digits = word.chars().take_while(|c| c.is_ascii_digit()).count();
+            let list_marker = match word[digits..].chars().next() {
+                Some('.') | Some(')') => block_start && digits > 0 && digits < 10,
+                _ => false,
+            };
+            for (i, c) in word.char_indices() {
+                match c {
+                    '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '&' => escaped.push('\\'),
+                    '#' | '-' | '+' | '=' if i == 0 && block_start => escaped.push('\\'),
+                    _ if i == digits && list_marker => escaped.push('\\'),
+                    _ => (),
+                }
+                escaped.push(c);
+            }
+            self.write_markup(&escaped);
+            self.pending_space = true;
+        }
+        // The last word isn't followed by whitespace unless the text ends in it.
+        if !text.ends_with(|c: char| c.is_ascii_whitespace()) {
+            self.pending_space = false;
+        }
+    }
+}
+
+fn attr_value<'a>(attrs: &[AttrRef<'a>], local: &str) -> Option<&'a str> {
+    attrs
+        .iter()
+        .find(|&&(name, _)| name.ns == ns!() && &*name.local == local)
+        .map(|&(_, value)| value)
+}
+
+fn longest_backtick_run(code: &str) -> usize {
+    let mut longest = 0;
+    let mut run = 0;
+    for c in code.chars() {
+        run = if c == '`' { run + 1 } else { 0 };
+        longest = longest.max(run);
+    }
+    longest
+}
+
+/// Whether `href` is relative or has one of `SAFE_URL_SCHEMES`.
+fn is_safe_link(href: &str) -> bool {
+    url_scheme(href).map_or(true, |scheme| SAFE_URL_SCHEMES.contains(&&*scheme))
+}
+
+fn heading_level(name: &QualName) -> Option<usize> {
+    match name.local {
+        local_name!("h1") => Some(1),
+        local_name!("h2") => Some(2),
+        local_name!("h3") => Some(3),
+        local_name!("h4") => Some(4),
+        local_name!("h5") => Some(5),
+        local_name!("h6") => Some(6),
+        _ => None,
+    }
+}
+
+impl Serializer for MarkdownSerializer {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        let attrs: Vec<AttrRef> = attrs.collect();
+        // Markup can't be written inside code; only the text is kept.
+        if name.ns != ns!(html) || self.code_text().is_some() {
+            if name.ns == ns!(html) && name.local == local_name!("br") {
+                if let Some(code) = self.code_text() {
+                    code.push('\n');
+                }
+            }
+            self.stack.push(Open::Other);
+            return Ok(());
+        }
+        let open = match name.local {
+            local_name!("script")
+            | local_name!("style")
+            | local_name!("template")
+            | local_name!("title") => Open::Ignored,
+            local_name!("ul") | local_name!("ol") => {
+                if self.in_item() {
+                    self.line_break();
+                } else {
+                    self.paragraph_break();
+                }
+                if name.local == local_name!("ol") {
+                    let start = attr_value(&attrs, "start").and_then(|s| s.trim().parse().ok());
+                    Open::List(Some(start.unwrap_or(1)))
+                } else {
+                    Open::List(None)
+                }
+            },
+            local_name!("li") => {
+                self.line_break();
+                let marker = match self.stack.last_mut() {
+                    Some(&mut Open::List(Some(ref mut next))) => {
+                        let marker = format!("{}. ", next);
+                        *next += 1;
+                        marker
+                    },
+                    _ => "- ".to_owned(),
+                };
+                self.write_markup(&marker);
+                self.after_marker = true;
+                Open::Item(marker.len())
+            },
+            local_name!("blockquote") => {
+                self.paragraph_break();
+                Open::Blockquote
+            },
+            local_name!("pre") => {
+                self.paragraph_break();
+                Open::Pre(String::new())
+            },
+            local_name!("code") => Open::Code(String::new()),
+            local_name!("strong") | local_name!("b") => {
+                self.write_markup("**");
+                Open::Strong
+            },
+            local_name!("em") | local_name!("i") => {
+                self.write_markup("*");
+                Open::Em
+            },
+            local_name!("a") => match attr_value(&attrs, "href") {
+                Some(href) if is_safe_link(href) => {
+                    self.write_markup("[");
+                    Open::Link(href.to_owned())
+                },
+                _ => Open::Other,
+            },
+            local_name!("br") => {
+                self.pending_space = false;
+                self.write_markup("\\");
+                self.pending_newline = true;
+                Open::Other
+            },
+            _ => match heading_level(&name) {
+                Some(level) => {
+                    self.paragraph_break();
+                    self.write_markup(&format!("{} ", "#".repeat(level)));
+                    self.after_marker = true;
+                    Open::Block
+                },
+                None if is_block_element(&name) => {
+                    self.paragraph_break();
+                    Open::Block
+                },
+                None => Open::Other,
+            },
+        };
+        self.stack.push(open);
+        Ok(())
+    }
+
+    fn end_elem(&mut self, _name: QualName) -> io::Result<()> {
+        match self.stack.pop() {
+            Some(Open::List(_)) if self.in_item() => {
+                self.after_marker = false;
+                self.line_break();
+            },
+            Some(Open::List(_)) | Some(Open::Blockquote) | Some(Open::Block) => {
+                self.after_marker = false;
+                self.paragraph_break();
+            },
+            Some(Open::Item(_)) => self.after_marker = false,
+            Some(Open::Pre(code)) => {
+                self.write_code_block(&code);
+                self.paragraph_break();
+            },
+            Some(Open::Code(code)) => self.write_code_span(&code),
+            Some(Open::Strong) => self.markdown.push_str("**"),
+            Some(Open::Em) => self.markdown.push('*'),
+            Some(Open::Link(href)) => {
+                self.markdown.push_str("](");
+                for c in href.chars() {
+                    match c {
+                        // Whitespace, controls and angle brackets would end the
+                        // destination or start HTML.
+                        c if c <= ' ' || c == '<' || c == '>' || c == '\u{7F}' => {
+                            self.markdown.push_str(&format!("%{:02X}", c as u32));
+                        },
+                        '(' | ')' | '\\' => {
+                            self.markdown.push('\\');
+                            self.markdown.push(c);
+                        },
+                        c => self.markdown.push(c),
+                    }
+                }
+                self.markdown.push(')');
+            },
+            Some(Open::Ignored) | Some(Open::Other) | None => (),
+        }
+        Ok(())
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        let ignored = self.is_open(|open| match *open {
+            Open::Ignored => true,
+            _ => false,
+        });
+        if ignored || text.is_empty() {
+            return Ok(());
+        }
+        if let Some(code) = self.code_text() {
+            code.push_str(text);
+            return Ok(());
+        }
+        self.write_inline(text);
+        Ok(())
+    }
+
+    fn write_comment(&mut self, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_doctype(&mut self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_processing_instruction(&mut self, _target: &str, _data: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -7,12 +7,66 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-pub use markup5ever::serialize::{AttrRef, Serialize, Serializer, TraversalScope};
-use std::collections::HashSet;
+pub use markup5ever::serialize::{AttrRef, EntitySpan, Serialize, Serializer, TraversalScope};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 use std::default::Default;
+use std::error::Error;
+use std::fmt;
 use std::io::{self, Write};
+use std::iter;
+use std::mem;
+use std::ops::RangeInclusive;
 
-use {LocalName, QualName};
+use self::recorded::Event;
+use data;
+use driver::{parse_fragment, ParseOpts};
+use interface::{NoQuirks, QuirksMode};
+use rcdom::RcDom;
+use tendril::TendrilSink;
+use {LocalName, Namespace, QualName};
+
+#[cfg(feature = "tokio")]
+pub use self::async_write::{serialize_async, SerializeAsync};
+pub use self::boundary::{serialize_with_boundaries, BoundaryHashSerializer};
+#[cfg(feature = "bytes")]
+pub use self::bytes_mut::serialize_into_bytes;
+pub use self::cancel::{serialize_cancellable, CancellableSerializer, Cancelled};
+pub use self::hook::ElementHook;
+pub use self::index::{serialize_text_index, TextIndexSerializer};
+pub use self::json::{serialize_json, JsonSerializer};
+pub use self::markdown::{serialize_markdown, MarkdownSerializer};
+pub use self::skip::{serialize_skipping, SkipSerializer};
+pub use self::sourcemap::{serialize_with_sourcemap, SourceMapSerializer};
+pub use self::style::StyleSanitizer;
+pub use self::summary::{serialize_summarized, SummarizingSerializer};
+pub use self::text::{serialize_text_content, TextOnlySerializer};
+pub use self::tokens::{serialize_tokens, TokenSerializer};
+pub use self::tracking::{serialize_without_tracking, StripTrackingSerializer};
+pub use self::validate::{Diagnostic, ValidatingSerializer};
+pub use self::writers::NullWriter;
+
+#[cfg(feature = "tokio")]
+mod async_write;
+mod boundary;
+#[cfg(feature = "bytes")]
+mod bytes_mut;
+mod cancel;
+mod hook;
+mod index;
+pub mod json;
+pub mod markdown;
+mod recorded;
+mod skip;
+mod sourcemap;
+mod style;
+mod summary;
+mod text;
+mod tokens;
+mod tracking;
+mod url;
+mod validate;
+pub mod writers;
 
 pub fn serialize<Wr, T>(writer: Wr, node: &T, opts: SerializeOpts) -> io::Result<()>
 where
@@ -20,148 +74,2275 @@ where
     T: Serialize,
 {
     let mut ser = HtmlSerializer::new(writer, opts.clone());
-    node.serialize(&mut ser, opts.traversal_scope)
+    try!(node.serialize(&mut ser, opts.traversal_scope));
+    ser.finish()
+}
+
+/// Serialize the children of `node` inside a `wrapper` element with
+/// `wrapper_attrs`, as if the tree had one around them, e.g. to embed a
+/// fragment in a container without changing the tree. The wrapper goes through
+/// the same options as any other element.
+pub fn serialize_wrapped<Wr, T>(
+    writer: Wr,
+    node: &T,
+    wrapper: QualName,
+    wrapper_attrs: &[AttrRef],
+    opts: SerializeOpts,
+) -> io::Result<()>
+where
+    Wr: Write,
+    T: Serialize,
+{
+    let mut ser = HtmlSerializer::new(writer, opts);
+    try!(ser.start_elem(wrapper.clone(), wrapper_attrs.iter().cloned()));
+    try!(node.serialize(&mut ser, TraversalScope::ChildrenOnly(None)));
+    try!(ser.end_elem(wrapper));
+    ser.finish()
+}
+
+/// Serialize `node`, passing the output to `on_chunk` in pieces of `chunk_size`
+/// bytes, e.g. to send it as a series of frames. Only the last chunk can be
+/// shorter. Panics if `chunk_size` is zero.
+pub fn serialize_chunked<T, F>(
+    node: &T,
+    opts: SerializeOpts,
+    chunk_size: usize,
+    on_chunk: F,
+) -> io::Result<()>
+where
+    T: Serialize,
+    F: FnMut(&[u8]),
+{
+    let mut writer = writers::ChunkedWriter::new(chunk_size, on_chunk);
+    try!(serialize(&mut writer, node, opts));
+    writer.flush()
+}
+
+/// Serialize `node` into a `String` that starts out with room for `capacity`
+/// bytes, to save reallocating it as it grows when the size of the output can
+/// be estimated.
+pub fn serialize_to_string_with_capacity<T: Serialize>(
+    node: &T,
+    opts: SerializeOpts,
+    capacity: usize,
+) -> io::Result<String> {
+    let mut output = Vec::with_capacity(capacity);
+    try!(serialize(&mut output, node, opts));
+    String::from_utf8(output).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Serialize `node` into a `String` of exactly the right size. This serializes
+/// `node` twice, first only to measure the output, so it trades time for memory.
+pub fn serialize_to_string_exact<T: Serialize>(
+    node: &T,
+    opts: SerializeOpts,
+) -> io::Result<String> {
+    let mut counter = NullWriter::new();
+    try!(serialize(&mut counter, node, opts.clone()));
+    serialize_to_string_with_capacity(node, opts, counter.count())
+}
+
+/// Serialize `node` and escape the result as an attribute value, so that it can
+/// be put between the quotes of an attribute such as `data-content="..."` and be
+/// read back as the serialized HTML.
+pub fn serialize_as_attribute_value<T: Serialize>(
+    node: &T,
+    opts: SerializeOpts,
+) -> io::Result<String> {
+    let mut writer = writers::EscapingWriter::new(vec![], true);
+    try!(serialize(&mut writer, node, opts));
+    let output = try!(writer.finish());
+    String::from_utf8(output).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Escape `value` to be put between the double quotes of an attribute, such
+/// as a serialized JSON object in `data-config="..."`, so that a parser reads
+/// it back unchanged: `&` becomes `&amp;`, `"` becomes `&quot;` and U+00A0
+/// becomes `&nbsp;`.
+pub fn escape_attribute_value(value: &str) -> String {
+    let mut writer = writers::EscapingWriter::new(vec![], true);
+    let output = writer
+        .write_all(value.as_bytes())
+        .and_then(|()| writer.finish())
+        .expect("writing to a Vec never fails");
+    String::from_utf8(output).expect("escaping keeps UTF-8 valid")
+}
+
+/// Parse `input` as the contents of a `<body>` and serialize it again with
+/// `opts`, which with the default options or a preset such as
+/// [`SerializeOpts::ugc`] removes or escapes anything that isn't allowed. The
+/// output is the sanitized HTML; an error only comes from options such as
+/// `strict_ascii` that make serialization fail.
+///
+/// [`SerializeOpts::ugc`]: struct.SerializeOpts.html#method.ugc
+pub fn sanitize_html(input: &str, opts: SerializeOpts) -> io::Result<String> {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one(input);
+    let root = &dom.document.children.borrow()[0];
+    serialize_to_string_with_capacity(root, opts, input.len())
+}
+
+/// Options for `HtmlSerializer`. The sets and maps in here are ordered ones, and
+/// the serializer never iterates over a hashed collection, so the output is the
+/// same byte for byte every time the same tree is serialized with equal options.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SerializeOpts {
+    /// Is scripting enabled? This should be the same as the `scripting_enabled`
+    /// the output will be parsed with. With scripting, the contents of a
+    /// `<noscript>` are read as raw text, and so are written unescaped; without
+    /// it they are markup, and any text in them is escaped. Default: true
+    pub scripting_enabled: bool,
+
+    /// Write the contents of a `<noscript>` for a parser that reads them as
+    /// markup (`Some(true)`), escaping any text in them, or as raw text
+    /// (`Some(false)`), writing it unescaped, whatever `scripting_enabled` is.
+    /// This is for output that is parsed differently from the input, such as
+    /// a page for crawlers, which run no scripts, built from a tree parsed
+    /// with scripting. With `None`, this follows `scripting_enabled`.
+    /// Default: None
+    pub serialize_noscript_as_elements: Option<bool>,
+
+    /// Serialize the root node? Default: ChildrenOnly
+    pub traversal_scope: TraversalScope,
+
+    /// Leave out the start and end tags of `<html>`, `<head>` and `<body>`
+    /// elements, while still writing their contents, for HTML to embed in
+    /// another page. Default: false
+    pub omit_html_body_tags: bool,
+
+    /// Wrap each run of text and inline elements directly inside a `<body>` in
+    /// a `<p>`, for normalizing loosely structured content. Block elements are
+    /// left as they are, and end the paragraph before them. Whitespace-only text
+    /// doesn't start a paragraph. Default: false
+    pub wrap_bare_text: bool,
+
+    /// If the serializer is asked to serialize an invalid tree, the default
+    /// behavior is to panic in the event that an `end_elem` is created without a
+    /// matching `start_elem`. Setting this to true will prevent those panics by
+    /// creating a default parent on the element stack. No extra start elem will
+    /// actually be written. Default: false
+    pub create_missing_parent: bool,
+
+    /// Produce balanced output from an unbalanced sequence of `start_elem` and
+    /// `end_elem` calls. An end tag that doesn't match any open element is dropped,
+    /// one that matches an element further up the stack first closes the elements
+    /// opened inside it, and `finish` writes end tags for anything still open.
+    /// Default: false
+    pub repair_tree: bool,
+
+    /// Write comment text exactly as given. By default every `--` in a comment is
+    /// broken up with a space, and a leading `>` or `->` is preceded by one, so that
+    /// the text can't end the comment early. Only enable this if the comments come
+    /// from a trusted source: a comment containing `-->` lets the rest of its text
+    /// be parsed as markup. Default: false
+    pub preserve_raw_comments: bool,
+
+    /// Elements whose tags are written as markup. The start and end tags of any
+    /// other element are escaped and written as text. `None` writes every element
+    /// as markup. Default: a small set of prose formatting elements
+    pub allowed_tags: Option<BTreeSet<LocalName>>,
+
+    /// Attributes to keep, keyed by element name. When set, an attribute is only
+    /// written if it is listed for its element or is in `always_allowed_attributes`.
+    /// Attributes are matched by local name. `None` writes every attribute.
+    /// Default: None
+    pub allowed_attributes: Option<BTreeMap<LocalName, BTreeSet<LocalName>>>,
+
+    /// Attributes that `allowed_attributes` keeps on every element, so that they
+    /// don't have to be repeated for each one. Default: `lang`, `dir`, `title`, `id`
+    pub always_allowed_attributes: BTreeSet<LocalName>,
+
+    /// Drop every attribute whose local name is `on` followed by anything, in any
+    /// case, such as `onclick` or `ONERROR`, whatever the whitelists say. These
+    /// are the event handlers, which run script, and HTML, SVG and MathML have no
+    /// other attributes that start with `on`; a custom one, such as `one="1"`, is
+    /// dropped too. Plain `on`, which AMP uses for actions, is kept.
+    /// Default: false
+    pub strip_event_handlers: bool,
+
+    /// The syntax to write. Default: `OutputSyntax::Html`
+    pub output_syntax: OutputSyntax,
+
+    /// Write non-ASCII characters in text and attribute values as numeric character
+    /// references, so that the output is plain ASCII. Raw text, such as the contents
+    /// of `<script>`, and comments can't contain references and are written
+    /// unchanged. Default: false
+    pub ascii_only: bool,
+
+    /// Together with `ascii_only`, fail with a [`NonAsciiError`] as soon as a
+    /// non-ASCII character would be written, anywhere in the output, instead of
+    /// writing a reference for it. Default: false
+    ///
+    /// [`NonAsciiError`]: struct.NonAsciiError.html
+    pub strict_ascii: bool,
+
+    /// Write attributes with an empty value as just their name, e.g. `alt`
+    /// instead of `alt=""`. Attributes like `value`, where readers commonly
+    /// treat an empty value differently from a missing one, keep the `=""`.
+    /// Ignored with the XHTML and XML syntaxes, since XML requires a value.
+    /// Default: false
+    pub minimize_empty_attributes: bool,
+
+    /// Leave `&` unescaped in text and attribute values where a parser reads it
+    /// back as `&` anyway, as in `a & b` or `?a=1&copy=2` in an attribute. It is
+    /// still escaped where it starts something that could be read as a
+    /// character reference, such as `&amp`, `&#39` or `&foo;`. Ignored with the
+    /// XHTML and XML syntaxes, since XML requires it to be escaped everywhere.
+    /// Default: false
+    pub minimal_amp_escaping: bool,
+
+    /// Escape `>` in the text of RCDATA elements (`<title>` and `<textarea>`).
+    /// Only `&` and `<` can be misread there, so turning this off gives more
+    /// readable output that parses back to the same text. Default: true
+    pub escape_gt_in_rcdata: bool,
+
+    /// URL schemes allowed in attributes that hold a single URL, such as `href`
+    /// and `src`. When set, such an attribute is dropped if its value has a scheme
+    /// that isn't listed; relative URLs are always kept. In a `srcset`, only the
+    /// image candidates with such URLs are left out. Schemes are matched
+    /// case-insensitively and must be given in lowercase. Default: None
+    pub allowed_url_schemes: Option<BTreeSet<String>>,
+
+    /// A `rel` value to write on every `<a>` element, replacing any `rel` it has,
+    /// e.g. `"nofollow"`. Default: None
+    pub link_rel: Option<String>,
+
+    /// An absolute URL to resolve relative URLs in attributes such as `href` and
+    /// `src` against, so that they still point to the same place when the markup
+    /// is moved elsewhere. URL scheme checks apply to the resolved URL.
+    /// Default: None
+    pub base_url: Option<String>,
+
+    /// A prefix, such as a CDN's `https://cdn.example`, to add to relative URLs in
+    /// `src` and `href` attributes, with a `/` between them if neither has one.
+    /// URLs with a scheme, protocol-relative ones such as `//host/x.png`, and
+    /// fragments such as `#top` are left alone. This happens before `base_url`
+    /// resolution, which then leaves the prefixed URLs alone. Default: None
+    pub src_prefix: Option<String>,
+
+    /// Attributes to write under a different name, e.g. `ng-click` as
+    /// `data-ng-click`. Renaming happens first, so the attribute whitelist, URL
+    /// handling and namespace prefix all apply to the new name. Default: empty
+    pub attr_name_map: BTreeMap<QualName, QualName>,
+
+    /// A [CSP nonce] to write on every `<script>` element, replacing any `nonce`
+    /// it has. Default: None
+    ///
+    /// [CSP nonce]: https://www.w3.org/TR/CSP3/#security-nonces
+    pub script_nonce: Option<String>,
+
+    /// Write `script_nonce` on `<style>` elements too. Default: false
+    pub style_nonce: bool,
+
+    /// Fail with an `InvalidData` error when a void element such as `<br>` is
+    /// given children, instead of silently dropping them. Default: false
+    pub strict_void: bool,
+
+    /// What to do with text that a parser would never have put where it is:
+    /// text, other than whitespace, directly inside `<html>` or `<head>`, or
+    /// outside any element in a document, such as between a doctype and the
+    /// `<html>`. Serializing a document is recognized by a doctype or an
+    /// `<html>` element at the top level, or by a known quirks mode, so this is
+    /// only a best effort; at the top level of a fragment, text is fine.
+    /// Default: `StrayText::Keep`
+    pub stray_text: StrayText,
+
+    /// HTML elements to treat as raw text, like `<script>` and `<xmp>`, on top of
+    /// the ones the HTML parser treats that way: their text is written without
+    /// escaping. Only list elements that whatever reads the output also parses as
+    /// raw text, or their contents can turn into markup. Note that `<listing>`
+    /// is not one: parsers handle it like `<pre>`. Default: empty
+    pub extra_raw_text_elements: BTreeSet<LocalName>,
+
+    /// Comments to keep, such as `more` for WordPress's `<!--more-->`. When set,
+    /// a comment is only written if its text, with surrounding whitespace
+    /// trimmed, is in the set; all others are dropped. `None` keeps every
+    /// comment. Default: None
+    pub preserved_comment_exact: Option<BTreeSet<String>>,
+
+    /// HTML elements to write under a different name, e.g. `b` as `strong`.
+    /// Both tags are renamed, and the tag and attribute whitelists apply to the
+    /// new name. Default: empty
+    pub tag_rewrite: BTreeMap<LocalName, LocalName>,
+
+    /// The most attributes to write on one element. Any more are dropped, with a
+    /// warning, to keep adversarial input from bloating the output. Attributes
+    /// removed by the whitelists don't count. Default: None
+    pub max_attributes_per_element: Option<usize>,
+
+    /// The most elements to serialize. Serialization fails with
+    /// `io::ErrorKind::InvalidData` at the element after that, to keep
+    /// adversarial trees that are wide rather than deep from taking too long.
+    /// Elements dropped by the whitelists count too. Default: None
+    pub max_total_elements: Option<usize>,
+
+    /// The longest attribute value to write, in bytes. Longer values are handled
+    /// as `long_attribute_values` says, with a warning. Default: None
+    pub max_attribute_value_len: Option<usize>,
+
+    /// What to do with attribute values longer than `max_attribute_value_len`.
+    /// Default: `LongAttributeValue::Truncate`
+    pub long_attribute_values: LongAttributeValue,
+
+    /// What to do with text nodes that consist only of whitespace. A run of
+    /// consecutive ones is treated as one, even if they are siblings separated by
+    /// dropped comments. Text inside `<pre>`, `<textarea>`, `<listing>` and raw
+    /// text elements is always kept, as are `<br>` and `<wbr>` elements and
+    /// non-breaking spaces, which are never taken for whitespace.
+    /// Default: `WhitespaceText::Preserve`
+    pub whitespace_text: WhitespaceText,
+
+    /// Write no-break spaces (U+00A0) as `&nbsp;`, or `&#xa0;` with the XHTML and
+    /// XML syntaxes.
+    /// Turn this off to write them as they are, e.g. so that the output can be
+    /// diffed byte for byte against a source that uses them literally. Other
+    /// characters are escaped either way, and `ascii_only` still applies.
+    /// Default: true
+    pub escape_nbsp: bool,
+
+    /// Write an element's `lang` attribute a second time as `xml:lang`, unless it
+    /// already has one, as polyglot documents that are also read as XML need both.
+    /// Default: false
+    pub mirror_lang_to_xml: bool,
+
+    /// Write elements and attributes in namespaces that HTML has no syntax for
+    /// with a prefix, declared by an `xmlns:` attribute, so that an XML parser
+    /// reads them back into the same namespace. The node's own prefix is used
+    /// when it has one, and otherwise one is generated. Without this, elements are
+    /// written with just their local name and attributes with an
+    /// `unknown_namespace:` prefix. This is always done with the XML syntax,
+    /// which also declares the `xlink` prefix where it is used. Default: false
+    pub lossless_namespaces: bool,
+
+    /// Attributes whose values are written in ASCII lowercase, to normalize
+    /// enumerated attributes such as `type="TEXT"`, whose values HTML matches
+    /// case-insensitively. Only attributes in no namespace are affected. Don't
+    /// list attributes whose case matters, such as `id` or `class`.
+    /// Default: empty
+    pub lowercase_value_attrs: BTreeSet<LocalName>,
+
+    /// Attributes whose values are lists of space-separated tokens to write
+    /// sorted, without duplicates and separated by single spaces, so that
+    /// `class="b a a"` is written as `class="a b"`, e.g. for diffing. Only
+    /// attributes in no namespace are affected.
+    /// [`token_list_attributes`] has the usual ones. Default: empty
+    ///
+    /// [`token_list_attributes`]: #method.token_list_attributes
+    pub canonicalize_token_attrs: BTreeSet<LocalName>,
+
+    /// Attributes in no namespace to write first, in this order, such as `id`
+    /// and `class`, for readability and stable diffs. The other attributes
+    /// follow in the order they are given in. Default: empty
+    pub attribute_order_priority: Vec<LocalName>,
+
+    /// Values of the `is` attribute, which makes an element a customized
+    /// built-in such as `<button is="fancy-button">`, that are allowed. An
+    /// element whose `is` value isn't listed is treated like one that
+    /// `allowed_tags` doesn't allow, even if its tag is allowed. `None` doesn't
+    /// look at `is`. Default: None
+    pub allowed_is_values: Option<BTreeSet<String>>,
+
+    /// URL prefixes that the `src` of a `<script>` must start with, such as
+    /// `https://cdn.ampproject.org/`. A script that doesn't match is treated
+    /// like an element that `allowed_tags` doesn't allow, except for JSON data
+    /// blocks, whose `type` is `application/json` or `application/ld+json`.
+    /// `None` allows every script. Default: None
+    pub allowed_script_srcs: Option<Vec<String>>,
+
+    /// Add `loading="lazy"` to `<img>` and `<iframe>` elements that don't have a
+    /// `loading` attribute, so that browsers defer loading them until they are
+    /// needed. Default: false
+    pub lazy_load_images: bool,
+
+    /// Code points to write as numeric character references in text and
+    /// attribute values, e.g. emoji (`0x1F300..=0x1FAFF`) for mail clients that
+    /// render them inconsistently. Like `ascii_only`, this doesn't apply to raw
+    /// text or comments. Default: empty
+    pub numeric_reference_ranges: Vec<RangeInclusive<u32>>,
+
+    /// What to do with the Unicode bidirectional embedding, override and
+    /// isolate controls, U+202A to U+202E and U+2066 to U+2069, in text and
+    /// attribute values. They can make text render in a different order than
+    /// it is read, as in "Trojan Source" spoofing; only `BidiControls::Strip`
+    /// prevents that in the rendered page. Like `ascii_only`, this doesn't apply
+    /// to raw text or comments.
+    /// Default: `BidiControls::Keep`
+    pub bidi_controls: BidiControls,
+
+    /// Write the digits of hexadecimal character references, such as those for
+    /// `ascii_only` or `numeric_reference_ranges`, in uppercase: `&#xAB;` instead
+    /// of `&#xab;`. The `x` is lowercase either way. Default: false
+    pub hex_uppercase: bool,
+
+    /// Which characters in text and attribute values are written as character
+    /// references, and how. Default: `EntityStyle::Minimal`
+    pub entity_style: EntityStyle,
+
+    /// Written in place of each element that `allowed_tags` doesn't allow,
+    /// instead of its escaped tags, with everything inside it dropped. It is
+    /// written as it is, so it can be markup such as `<!--removed-->` as well as
+    /// text. Default: None
+    pub disallowed_replacement: Option<String>,
+
+    /// Put block-level elements, comments and doctypes on their own lines,
+    /// indented by their depth, and drop the whitespace-only text that was there.
+    /// Whitespace between inline elements is kept, and nothing changes inside
+    /// elements whose whitespace is significant, such as `<pre>`. This is meant
+    /// for reading; the added whitespace can change how the document renders.
+    /// Default: false
+    pub pretty_print: bool,
+
+    /// One level of indentation with `pretty_print`, such as `"\t"`.
+    /// Default: two spaces
+    pub indent_with: String,
+
+    /// HTML elements that `pretty_print` leaves on the line they are on, such as
+    /// `a` or `span`; all others start a new line. Elements outside the HTML
+    /// namespace are always inline. Default: the phrasing elements, plus a few
+    /// such as `iframe` and `video` that are usually used like them
+    pub inline_elements: BTreeSet<LocalName>,
+
+    /// HTML elements whose contents `pretty_print` leaves as they are, as if
+    /// they were preformatted, such as `p` to keep a paragraph of mixed inline
+    /// content on one line even if some of it is block-level. The elements
+    /// themselves are still put on their own lines. Unlike in `<pre>`,
+    /// `whitespace_text` still applies. Default: empty
+    pub no_indent_children: BTreeSet<LocalName>,
+
+    /// With `pretty_print`, write the attributes of a start tag that starts a
+    /// line one per line, lined up after the tag name, if the tag would make
+    /// its line longer than this many characters. The length is estimated from
+    /// the attributes before they are escaped or filtered, and inline elements,
+    /// whose place on their line isn't known, are never wrapped. Default: None
+    pub max_line_length: Option<usize>,
+
+    /// Write void elements whose start tag was self-closing in the source, such
+    /// as `<br/>`, the same way, as far as the tree records it, so that an
+    /// edited document differs from its source as little as possible. Default: false
+    pub preserve_self_closing: bool,
+
+    /// Leave out attributes whose value is the one an HTML element has anyway,
+    /// such as `type="text"` on `<input>` or `method="get"` on `<form>`, to
+    /// shorten form-heavy output. Values are compared ASCII case-insensitively.
+    /// Default: false
+    pub drop_default_attributes: bool,
+
+    /// Comments that mark a position for an editor, such as `cursor` for
+    /// `<!--cursor-->`. A comment whose text is listed is always written, whatever
+    /// `preserved_comment_exact` says, and `pretty_print` leaves it on the line it
+    /// is on. Default: empty
+    pub editor_markers: BTreeSet<String>,
+
+    /// Write text and attribute values in Unicode Normalization Form C, so that
+    /// canonically equivalent strings, such as `e` followed by a combining acute
+    /// accent and a precomposed `é`, are written the same way. Raw text, such as
+    /// the contents of `<script>`, and comments are left alone. Requires the
+    /// `unicode-normalization` feature. Default: false
+    #[cfg(feature = "unicode-normalization")]
+    pub normalize_nfc: bool,
+
+    /// Pass the values of `style` attributes through this, to keep only the
+    /// declarations it allows rather than the whole attribute or none of it. The
+    /// attribute is dropped if nothing is left. It must still be allowed by
+    /// `allowed_attributes`. Default: None
+    pub style_sanitizer: Option<StyleSanitizer>,
+
+    /// What to do with an `id` attribute whose value was already written earlier
+    /// in the output, e.g. in a document put together from fragments. Default:
+    /// `DuplicateIds::Keep`
+    pub duplicate_ids: DuplicateIds,
+
+    /// Add `<meta charset="utf-8">` as the first child of the `<head>` of a full
+    /// document, one whose top-level element is `<html>`, unless the `<head>`
+    /// already declares a character encoding with `<meta charset>` or
+    /// `<meta http-equiv="content-type">`. The contents of the `<head>` are held
+    /// back until its end to find out. Default: false
+    pub ensure_charset: bool,
+
+    /// Leave out the doctype of a document that was parsed in quirks or
+    /// limited-quirks mode, since writing it as `<!DOCTYPE html>` would switch
+    /// a browser reading the output to no-quirks mode. Only applies when the
+    /// quirks mode is known, e.g. when serializing an `RcDom` rather than its
+    /// document node. Default: false
+    pub doctype_only_in_no_quirks: bool,
+
+    /// Called with the name and attributes of each element that is written,
+    /// which it may change. What it leaves is then treated like any other
+    /// attributes, so that added ones must still be allowed by
+    /// `allowed_attributes`. Default: None
+    pub element_hook: Option<ElementHook>,
+}
+
+/// The syntax `HtmlSerializer` writes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputSyntax {
+    /// The HTML syntax.
+    Html,
+    /// [Polyglot markup], which parses to the same tree as HTML and as XML:
+    /// void elements are self-closed, HTML element and attribute names are
+    /// lowercased, the `html`, `svg` and `math` elements declare their namespace,
+    /// `&nbsp;` is written as a numeric reference, and `<script>` or `<style>`
+    /// content containing `<` or `&` is wrapped in a commented-out CDATA section.
+    ///
+    /// [Polyglot markup]: https://www.w3.org/TR/html-polyglot/
+    Xhtml,
+    /// XML, for consumers that will only ever read the output as XML. Like
+    /// `Xhtml`, except that the content of raw text elements such as `<script>`
+    /// is escaped like any other text, which an HTML parser wouldn't undo.
+    Xml,
+}
+
+/// How `HtmlSerializer` writes whitespace-only text nodes.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WhitespaceText {
+    /// Write them unchanged.
+    Preserve,
+    /// Leave them out.
+    Remove,
+    /// Write each run of them as a single space.
+    SingleSpace,
+}
+
+/// How `HtmlSerializer` escapes `&`, `"`, `'`, `<` and `>` in text and
+/// attribute values.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EntityStyle {
+    /// Escape only what the HTML spec's serialization algorithm does: `&`
+    /// everywhere, `<` and `>` in text, and `"` in attribute values, with named
+    /// references.
+    Minimal,
+    /// Escape all five everywhere, with `&amp;`, `&quot;`, `&apos;`, `&lt;` and
+    /// `&gt;`.
+    Named,
+    /// Escape all five everywhere, with hexadecimal references such as `&#x27;`.
+    /// No-break spaces are also written as `&#xa0;` with `escape_nbsp`.
+    Numeric,
+}
+
+/// How `HtmlSerializer` writes an `id` attribute whose value is already the
+/// `id` of an earlier element.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DuplicateIds {
+    /// Write it unchanged.
+    Keep,
+    /// Leave the attribute out.
+    Drop,
+    /// Add the first of `-2`, `-3` and so on that makes it unique, so that
+    /// `id="a"` is written as `id="a-2"` the second time.
+    Suffix,
+}
+
+/// What `HtmlSerializer` does with text where a parser wouldn't have put it.
+/// See `SerializeOpts::stray_text`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StrayText {
+    /// Write it.
+    Keep,
+    /// Write it, with a warning.
+    Warn,
+    /// Leave it out, with a warning.
+    Drop,
+    /// Fail with `io::ErrorKind::InvalidData`.
+    Error,
+}
+
+/// How `HtmlSerializer` writes an attribute value that is longer than
+/// `max_attribute_value_len`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LongAttributeValue {
+    /// Cut it at the last character boundary within the limit.
+    Truncate,
+    /// Cut it like `Truncate` and add `…`, which may take it up to three bytes
+    /// over the limit.
+    TruncateWithEllipsis,
+    /// Leave the attribute out.
+    Drop,
+}
+
+/// How `HtmlSerializer` writes the bidirectional control characters of
+/// `bidi_controls`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BidiControls {
+    /// Write them as they are.
+    Keep,
+    /// Write them as numeric character references. This only makes them
+    /// visible to someone reading the source, such as a reviewer: a parser reads
+    /// the references back as the same characters, which still reorder the text
+    /// when it is rendered. Use `Strip` to protect what readers of the page see.
+    Escape,
+    /// Leave them out, so that they can't reorder the rendered text.
+    Strip,
+}
+
+impl Default for SerializeOpts {
+    fn default() -> SerializeOpts {
+        SerializeOpts {
+            scripting_enabled: true,
+            serialize_noscript_as_elements: None,
+            traversal_scope: TraversalScope::ChildrenOnly(None),
+            omit_html_body_tags: false,
+            wrap_bare_text: false,
+            create_missing_parent: false,
+            repair_tree: false,
+            preserve_raw_comments: false,
+            allowed_tags: Some(default_allowed_tags()),
+            allowed_attributes: None,
+            always_allowed_attributes: [
+                local_name!("lang"),
+                local_name!("dir"),
+                local_name!("title"),
+                local_name!("id"),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            strip_event_handlers: false,
+            output_syntax: OutputSyntax::Html,
+            ascii_only: false,
+            strict_ascii: false,
+            minimize_empty_attributes: false,
+            minimal_amp_escaping: false,
+            escape_gt_in_rcdata: true,
+            allowed_url_schemes: None,
+            link_rel: None,
+            base_url: None,
+            src_prefix: None,
+            attr_name_map: BTreeMap::new(),
+            script_nonce: None,
+            style_nonce: false,
+            strict_void: false,
+            stray_text: StrayText::Keep,
+            extra_raw_text_elements: BTreeSet::new(),
+            preserved_comment_exact: None,
+            tag_rewrite: BTreeMap::new(),
+            max_attributes_per_element: None,
+            max_total_elements: None,
+            max_attribute_value_len: None,
+            long_attribute_values: LongAttributeValue::Truncate,
+            whitespace_text: WhitespaceText::Preserve,
+            escape_nbsp: true,
+            mirror_lang_to_xml: false,
+            lossless_namespaces: false,
+            lowercase_value_attrs: BTreeSet::new(),
+            canonicalize_token_attrs: BTreeSet::new(),
+            attribute_order_priority: vec![],
+            allowed_is_values: None,
+            allowed_script_srcs: None,
+            lazy_load_images: false,
+            numeric_reference_ranges: vec![],
+            bidi_controls: BidiControls::Keep,
+            hex_uppercase: false,
+            entity_style: EntityStyle::Minimal,
+            disallowed_replacement: None,
+            pretty_print: false,
+            indent_with: "  ".to_string(),
+            no_indent_children: BTreeSet::new(),
+            max_line_length: None,
+            inline_elements: default_inline_elements(),
+            preserve_self_closing: false,
+            drop_default_attributes: false,
+            editor_markers: BTreeSet::new(),
+            #[cfg(feature = "unicode-normalization")]
+            normalize_nfc: false,
+            style_sanitizer: None,
+            duplicate_ids: DuplicateIds::Keep,
+            ensure_charset: false,
+            doctype_only_in_no_quirks: false,
+            element_hook: None,
+        }
+    }
+}
+
+impl SerializeOpts {
+    /// Options that follow the WHATWG [HTML fragment serialization algorithm],
+    /// for output equivalent to a browser's `innerHTML`: every element is written
+    /// as markup, with all its attributes, and comments are written unchanged.
+    /// Note that the algorithm no longer adds a line feed after `<pre>`,
+    /// `<textarea>` or `<listing>` to protect one at the start of their text.
+    ///
+    /// [HTML fragment serialization algorithm]: https://html.spec.whatwg.org/multipage/parsing.html#serialising-html-fragments
+    pub fn spec_compliant() -> SerializeOpts {
+        SerializeOpts {
+            allowed_tags: None,
+            preserve_raw_comments: true,
+            ..Default::default()
+        }
+    }
+
+    /// Attributes whose values are unordered sets of tokens, for
+    /// `canonicalize_token_attrs`: `class`, `rel` and `sandbox`.
+    pub fn token_list_attributes() -> BTreeSet<LocalName> {
+        [
+            local_name!("class"),
+            local_name!("rel"),
+            local_name!("sandbox"),
+        ]
+        .iter()
+        .cloned()
+        .collect()
+    }
+
+    /// Options for writing XHTML5 polyglot markup. The tag and attribute whitelists
+    /// are the defaults; set `allowed_tags` to `None` to serialize whole documents.
+    pub fn polyglot() -> SerializeOpts {
+        SerializeOpts {
+            output_syntax: OutputSyntax::Xhtml,
+            ..Default::default()
+        }
+    }
+}
+
+impl SerializeOpts {
+    /// Options for user-generated content such as comments, approximating what
+    /// GitHub allows in rendered Markdown.
+    ///
+    /// These elements are written as markup: `p`, `br`, `hr`, `div`, `span`,
+    /// `blockquote`, `pre`, `code`, `kbd`, `samp`, `var`, `h1` to `h6`, `strong`,
+    /// `b`, `em`, `i`, `del`, `s`, `ins`, `sup`, `sub`, `a`, `img`, `picture`,
+    /// `source`, `ul`, `ol`, `li`, `dl`, `dt`, `dd`, `details`, `summary`,
+    /// `table`, `thead`, `tbody`, `tfoot`, `tr`, `th` and `td`. Every other tag
+    /// is escaped.
+    ///
+    /// The only attributes kept are `title`, `lang` and `dir` everywhere, `href`
+    /// on `a`, `src`, `srcset`, `sizes`, `alt`, `width` and `height` on `img`,
+    /// `srcset`, `sizes`, `media`, `type`, `width` and `height` on `source`,
+    /// `start` on `ol`, and `align`, `colspan` and `rowspan` on `th` and `td`.
+    /// URLs must be relative or use `http`, `https` or `mailto`, also in a
+    /// `srcset`, and links get `rel="nofollow noopener"`.
+    pub fn ugc() -> SerializeOpts {
+        SerializeOpts {
+            allowed_tags: Some(ugc_allowed_tags()),
+            allowed_attributes: Some(ugc_allowed_attributes()),
+            always_allowed_attributes: [
+                local_name!("title"),
+                local_name!("lang"),
+                local_name!("dir"),
+            ]
+            .iter()
+            .cloned()
+            .collect(),
+            allowed_url_schemes: Some(SAFE_URL_SCHEMES.iter().map(|s| s.to_string()).collect()),
+            link_rel: Some("nofollow noopener".to_string()),
+            ..Default::default()
+        }
+    }
+
+    /// Options for the subset of HTML that [AMP] pages may use.
+    ///
+    /// `<img>`, `<video>`, `<audio>` and `<iframe>` are written as `<amp-img>`,
+    /// `<amp-video>`, `<amp-audio>` and `<amp-iframe>`. Elements AMP doesn't
+    /// allow, such as `<form>` and `<object>`, are left out with everything
+    /// inside them, and so is every `<script>` other than AMP's own, loaded from
+    /// `https://cdn.ampproject.org/`, and JSON data. Event handler attributes are
+    /// dropped. Other AMP components than those above are left out too; add
+    /// them to `allowed_tags` as needed. This doesn't check everything the AMP
+    /// validator does, such as the required markup in the `<head>`.
+    ///
+    /// [AMP]: https://amp.dev/documentation/guides-and-tutorials/learn/spec/amphtml
+    pub fn amp() -> SerializeOpts {
+        let mut tag_rewrite = BTreeMap::new();
+        for &tag in &["img", "video", "audio", "iframe"] {
+            tag_rewrite.insert(
+                LocalName::from(tag),
+                LocalName::from(format!("amp-{}", tag)),
+            );
+        }
+        SerializeOpts {
+            allowed_tags: Some(amp_allowed_tags()),
+            allowed_script_srcs: Some(vec!["https://cdn.ampproject.org/".to_string()]),
+            disallowed_replacement: Some(String::new()),
+            tag_rewrite: tag_rewrite,
+            strip_event_handlers: true,
+            ..Default::default()
+        }
+    }
 }
 
-#[derive(Clone)]
-pub struct SerializeOpts {
-    /// Is scripting enabled?
-    pub scripting_enabled: bool,
+fn amp_allowed_tags() -> BTreeSet<LocalName> {
+    [
+        "html",
+        "head",
+        "title",
+        "meta",
+        "link",
+        "style",
+        "script",
+        "noscript",
+        "body",
+        "article",
+        "section",
+        "nav",
+        "aside",
+        "header",
+        "footer",
+        "main",
+        "address",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "p",
+        "hr",
+        "pre",
+        "blockquote",
+        "ol",
+        "ul",
+        "li",
+        "dl",
+        "dt",
+        "dd",
+        "figure",
+        "figcaption",
+        "div",
+        "a",
+        "em",
+        "strong",
+        "small",
+        "s",
+        "cite",
+        "q",
+        "dfn",
+        "abbr",
+        "data",
+        "time",
+        "code",
+        "var",
+        "samp",
+        "kbd",
+        "sub",
+        "sup",
+        "i",
+        "b",
+        "u",
+        "mark",
+        "ruby",
+        "rb",
+        "rt",
+        "rtc",
+        "rp",
+        "bdi",
+        "bdo",
+        "span",
+        "br",
+        "wbr",
+        "ins",
+        "del",
+        "source",
+        "track",
+        "table",
+        "caption",
+        "colgroup",
+        "col",
+        "tbody",
+        "thead",
+        "tfoot",
+        "tr",
+        "td",
+        "th",
+        "template",
+        "slot",
+        "amp-img",
+        "amp-video",
+        "amp-audio",
+        "amp-iframe",
+    ]
+    .iter()
+    .map(|&tag| LocalName::from(tag))
+    .collect()
+}
+
+/// The URL schemes that `SerializeOpts::ugc` allows, and that Markdown links
+/// may have.
+const SAFE_URL_SCHEMES: &[&str] = &["http", "https", "mailto"];
+
+fn ugc_allowed_tags() -> BTreeSet<LocalName> {
+    [
+        local_name!("p"),
+        local_name!("br"),
+        local_name!("hr"),
+        local_name!("div"),
+        local_name!("span"),
+        local_name!("blockquote"),
+        local_name!("pre"),
+        local_name!("code"),
+        local_name!("kbd"),
+        local_name!("samp"),
+        local_name!("var"),
+        local_name!("h1"),
+        local_name!("h2"),
+        local_name!("h3"),
+        local_name!("h4"),
+        local_name!("h5"),
+        local_name!("h6"),
+        local_name!("strong"),
+        local_name!("b"),
+        local_name!("em"),
+        local_name!("i"),
+        local_name!("del"),
+        local_name!("s"),
+        local_name!("ins"),
+        local_name!("sup"),
+        local_name!("sub"),
+        local_name!("a"),
+        local_name!("img"),
+        local_name!("picture"),
+        local_name!("source"),
+        local_name!("ul"),
+        local_name!("ol"),
+        local_name!("li"),
+        local_name!("dl"),
+        local_name!("dt"),
+        local_name!("dd"),
+        local_name!("details"),
+        local_name!("summary"),
+        local_name!("table"),
+        local_name!("thead"),
+        local_name!("tbody"),
+        local_name!("tfoot"),
+        local_name!("tr"),
+        local_name!("th"),
+        local_name!("td"),
+    ]
+    .iter()
+    .cloned()
+    .collect()
+}
+
+fn ugc_allowed_attributes() -> BTreeMap<LocalName, BTreeSet<LocalName>> {
+    let cell = || {
+        [
+            local_name!("align"),
+            local_name!("colspan"),
+            local_name!("rowspan"),
+        ]
+        .iter()
+        .cloned()
+        .collect()
+    };
+    let mut allowed = BTreeMap::new();
+    allowed.insert(
+        local_name!("a"),
+        [local_name!("href")].iter().cloned().collect(),
+    );
+    allowed.insert(
+        local_name!("img"),
+        [
+            local_name!("src"),
+            local_name!("srcset"),
+            local_name!("sizes"),
+            local_name!("alt"),
+            local_name!("width"),
+            local_name!("height"),
+        ]
+        .iter()
+        .cloned()
+        .collect(),
+    );
+    allowed.insert(
+        local_name!("source"),
+        [
+            local_name!("srcset"),
+            local_name!("sizes"),
+            local_name!("media"),
+            local_name!("type"),
+            local_name!("width"),
+            local_name!("height"),
+        ]
+        .iter()
+        .cloned()
+        .collect(),
+    );
+    allowed.insert(
+        local_name!("ol"),
+        [local_name!("start")].iter().cloned().collect(),
+    );
+    allowed.insert(local_name!("th"), cell());
+    allowed.insert(local_name!("td"), cell());
+    allowed
+}
+
+fn default_allowed_tags() -> BTreeSet<LocalName> {
+    [
+        local_name!("p"),
+        local_name!("br"),
+        local_name!("strong"),
+        local_name!("em"),
+        local_name!("del"),
+        local_name!("blockquote"),
+        local_name!("code"),
+        local_name!("pre"),
+        local_name!("h1"),
+        local_name!("h2"),
+        local_name!("h3"),
+        local_name!("h4"),
+        local_name!("h5"),
+        local_name!("h6"),
+        local_name!("a"),
+        local_name!("ul"),
+        local_name!("ol"),
+        local_name!("li"),
+        local_name!("hr"),
+    ]
+    .iter()
+    .cloned()
+    .collect()
+}
+
+/// The error, wrapped in an `io::Error` of kind `InvalidData`, that serialization
+/// fails with when `strict_ascii` is set and a non-ASCII character is found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NonAsciiError {
+    /// The offending character.
+    pub character: char,
+    /// Its byte offset within the text, comment, name or attribute value that
+    /// contains it.
+    pub offset: usize,
+}
+
+impl fmt::Display for NonAsciiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "non-ASCII character {:?} (U+{:04X}) at offset {}",
+            self.character, self.character as u32, self.offset
+        )
+    }
+}
+
+impl Error for NonAsciiError {
+    fn description(&self) -> &str {
+        "non-ASCII character in ASCII-only output"
+    }
+}
+
+#[derive(Default)]
+struct ElemInfo {
+    name: Option<QualName>,
+    html_name: Option<LocalName>,
+    ignore_children: bool,
+    processed_first_child: bool,
+    /// Whether whitespace in this element's text is significant.
+    preserve_whitespace: bool,
+    /// Whether `pretty_print` has put a child of this element on its own line.
+    has_block_children: bool,
+    /// Whether `pretty_print` leaves this element's contents alone, because of
+    /// `no_indent_children`.
+    no_indent: bool,
+    /// Whether this element's tags are escaped, because it isn't allowed.
+    escaped: bool,
+    /// Whether this element's tags are left out, because of
+    /// `omit_html_body_tags`.
+    omitted: bool,
+    /// Whether this is a `<p>` added by `wrap_bare_text`.
+    wrapped_text: bool,
+    /// The prefixes declared on this element by `lossless_namespaces`.
+    namespaces: Vec<(String, Namespace)>,
+}
+
+pub struct HtmlSerializer<Wr: Write> {
+    pub writer: Wr,
+    opts: SerializeOpts,
+    stack: Vec<ElemInfo>,
+    /// Whitespace-only text held back by `whitespace_text` or `pretty_print`.
+    pending_whitespace: Option<String>,
+    /// Whether nothing has been written yet.
+    at_start: bool,
+    /// Whether `pretty_print` should start a new line before the next node.
+    line_pending: bool,
+    /// The namespaces given generated prefixes by `lossless_namespaces`, in order.
+    generated_prefixes: Vec<Namespace>,
+    /// Set once a `<plaintext>` start tag has been written, after which a parser
+    /// reads everything as its text: the number of elements at the bottom of the
+    /// stack that it would never see closed, which are the `<plaintext>` and
+    /// the elements around it.
+    plaintext_open: Option<usize>,
+    /// The `id`s written so far, for `duplicate_ids`.
+    seen_ids: BTreeSet<String>,
+    /// The contents of the `<head>` held back by `ensure_charset`, while it is
+    /// open.
+    head_events: Option<Vec<Event>>,
+    /// The number of elements open inside the held back `<head>`.
+    head_depth: usize,
+    /// The quirks mode of the document, if the node being serialized knows it.
+    quirks_mode: Option<QuirksMode>,
+    /// The number of elements started so far, for `max_total_elements`.
+    elements_started: usize,
+    /// Whether the next element started is one that the options add, such as
+    /// the `<p>` of `wrap_bare_text`, which isn't counted in `elements_started`.
+    adding_element: bool,
+    /// Whether a whole document is being serialized, as far as `stray_text`
+    /// can tell.
+    in_document: bool,
+}
+
+fn tagname(name: &QualName) -> LocalName {
+    match name.ns {
+        ns!(html) | ns!(mathml) | ns!(svg) => (),
+        ref ns => {
+            // FIXME(#122)
+            warn!("node with weird namespace {:?}", ns);
+        },
+    }
+
+    name.local.clone()
+}
+
+fn is_void_element(name: &QualName) -> bool {
+    name.ns == ns!(html)
+        && match name.local {
+            local_name!("area")
+            | local_name!("base")
+            | local_name!("basefont")
+            | local_name!("bgsound")
+            | local_name!("br")
+            | local_name!("col")
+            | local_name!("embed")
+            | local_name!("frame")
+            | local_name!("hr")
+            | local_name!("img")
+            | local_name!("input")
+            | local_name!("keygen")
+            | local_name!("link")
+            | local_name!("meta")
+            | local_name!("param")
+            | local_name!("source")
+            | local_name!("track")
+            | local_name!("wbr") => true,
+            _ => false,
+        }
+}
+
+/// How the text inside an element is written, and read back by a parser.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextMode {
+    /// Escaped text, among child elements.
+    Normal,
+    /// Escaped text only, as in `<title>` and `<textarea>`: a parser reads `&amp;`
+    /// back as `&`, but `<b>` as text rather than a tag.
+    Rcdata,
+    /// Text written as it is, as in `<script>` and `<style>`, which must not
+    /// contain the element's end tag.
+    RawText,
+}
+
+/// The text mode the serializer writes the text of `name` in. `<noscript>` is
+/// raw text, as it is with `scripting_enabled`, and `extra_raw_text_elements` is
+/// not taken into account. Without `scripting_enabled`, the text of a
+/// `<noscript>` is `TextMode::Normal` instead.
+pub fn text_mode(name: &QualName) -> TextMode {
+    match name.ns {
+        ns!(html) => html_text_mode(&name.local),
+        _ => TextMode::Normal,
+    }
+}
+
+fn html_text_mode(tag: &LocalName) -> TextMode {
+    match *tag {
+        local_name!("style")
+        | local_name!("script")
+        | local_name!("xmp")
+        | local_name!("iframe")
+        | local_name!("noembed")
+        | local_name!("noframes")
+        | local_name!("plaintext")
+        | local_name!("noscript") => TextMode::RawText,
+        local_name!("title") | local_name!("textarea") => TextMode::Rcdata,
+        _ => TextMode::Normal,
+    }
+}
+
+fn is_html_body_tag(name: &QualName) -> bool {
+    name.ns == ns!(html)
+        && match name.local {
+            local_name!("html") | local_name!("head") | local_name!("body") => true,
+            _ => false,
+        }
+}
+
+/// Whether `name` is in a namespace that the element or attribute (as `attr`
+/// says) can't be written in without an `xmlns:` declaration.
+fn has_unknown_namespace(name: &QualName, attr: bool) -> bool {
+    match name.ns {
+        ns!() => false,
+        ns!(html) | ns!(svg) | ns!(mathml) => attr,
+        ns!(xml) | ns!(xmlns) | ns!(xlink) => !attr,
+        _ => true,
+    }
+}
+
+/// Whether `attrs` has no `is` attribute that `allowed_is_values` rejects.
+fn is_custom_element_allowed(opts: &SerializeOpts, attrs: &[AttrRef]) -> bool {
+    let allowed = match opts.allowed_is_values {
+        Some(ref allowed) => allowed,
+        None => return true,
+    };
+    attrs
+        .iter()
+        .filter(|&&(name, _)| name.ns == ns!() && name.local == local_name!("is"))
+        .all(|&(_, value)| allowed.contains(value))
+}
+
+/// Whether `elem` isn't a `<script>` that `allowed_script_srcs` rejects.
+fn is_script_allowed(opts: &SerializeOpts, elem: &QualName, attrs: &[AttrRef]) -> bool {
+    let allowed = match opts.allowed_script_srcs {
+        Some(ref allowed) if elem.ns == ns!(html) && elem.local == local_name!("script") => allowed,
+        _ => return true,
+    };
+    let attr = |local: LocalName| {
+        attrs
+            .iter()
+            .find(|&&(name, _)| name.ns == ns!() && name.local == local)
+            .map(|&(_, value)| value.trim())
+    };
+    match attr(local_name!("type")) {
+        Some(ty)
+            if ty.eq_ignore_ascii_case("application/json")
+                || ty.eq_ignore_ascii_case("application/ld+json") =>
+        {
+            return true
+        },
+        _ => (),
+    }
+    attr(local_name!("src")).map_or(false, |src| {
+        allowed.iter().any(|prefix| src.starts_with(&**prefix))
+    })
+}
+
+fn default_inline_elements() -> BTreeSet<LocalName> {
+    [
+        local_name!("a"),
+        local_name!("abbr"),
+        local_name!("acronym"),
+        local_name!("audio"),
+        local_name!("b"),
+        local_name!("bdi"),
+        local_name!("bdo"),
+        local_name!("big"),
+        local_name!("br"),
+        local_name!("button"),
+        local_name!("canvas"),
+        local_name!("cite"),
+        local_name!("code"),
+        local_name!("data"),
+        local_name!("datalist"),
+        local_name!("del"),
+        local_name!("dfn"),
+        local_name!("em"),
+        local_name!("embed"),
+        local_name!("font"),
+        local_name!("i"),
+        local_name!("iframe"),
+        local_name!("img"),
+        local_name!("input"),
+        local_name!("ins"),
+        local_name!("kbd"),
+        local_name!("label"),
+        local_name!("map"),
+        local_name!("mark"),
+        local_name!("meter"),
+        local_name!("nobr"),
+        local_name!("noscript"),
+        local_name!("object"),
+        local_name!("output"),
+        local_name!("picture"),
+        local_name!("progress"),
+        local_name!("q"),
+        local_name!("ruby"),
+        local_name!("s"),
+        local_name!("samp"),
+        local_name!("script"),
+        local_name!("select"),
+        local_name!("small"),
+        local_name!("span"),
+        local_name!("strong"),
+        local_name!("sub"),
+        local_name!("sup"),
+        local_name!("svg"),
+        local_name!("math"),
+        local_name!("template"),
+        local_name!("textarea"),
+        local_name!("time"),
+        local_name!("tt"),
+        local_name!("u"),
+        local_name!("var"),
+        local_name!("video"),
+        local_name!("wbr"),
+    ]
+    .iter()
+    .cloned()
+    .collect()
+}
+
+/// Elements whose text keeps its whitespace when `whitespace_text` is set.
+fn preserves_whitespace(name: &LocalName) -> bool {
+    match *name {
+        local_name!("pre")
+        | local_name!("textarea")
+        | local_name!("listing")
+        | local_name!("plaintext")
+        | local_name!("xmp")
+        | local_name!("script")
+        | local_name!("style") => true,
+        _ => false,
+    }
+}
+
+/// Whether `c` is whitespace that `whitespace_text` and `pretty_print` may
+/// collapse. This is ASCII whitespace only, so that non-breaking spaces are kept.
+fn is_collapsible_whitespace(c: char) -> bool {
+    match c {
+        ' ' | '\t' | '\n' | '\x0C' | '\r' => true,
+        _ => false,
+    }
+}
+
+/// The bidirectional controls that `bidi_controls` applies to.
+fn is_bidi_control(c: char) -> bool {
+    match c {
+        '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' => true,
+        _ => false,
+    }
+}
+
+/// Attributes that hold a single URL, checked against `allowed_url_schemes`.
+fn is_url_attribute(name: &QualName) -> bool {
+    match name.ns {
+        ns!() => match name.local {
+            local_name!("href")
+            | local_name!("src")
+            | local_name!("cite")
+            | local_name!("action")
+            | local_name!("formaction")
+            | local_name!("poster")
+            | local_name!("background")
+            | local_name!("longdesc")
+            | local_name!("data") => true,
+            _ => false,
+        },
+        ns!(xlink) => name.local == local_name!("href"),
+        _ => false,
+    }
+}
+
+/// The lowercased scheme of `url`, or `None` if it is relative.
+fn url_scheme(url: &str) -> Option<String> {
+    // Browsers ignore leading and trailing spaces and control characters, and tabs
+    // and newlines anywhere, so `" java\tscript:"` is still a `javascript:` URL.
+    let url: String = url
+        .trim_matches(|c: char| c <= ' ')
+        .chars()
+        .filter(|&c| c != '\t' && c != '\n' && c != '\r')
+        .collect();
+    let end = match url.find(|c| c == ':' || c == '/' || c == '?' || c == '#') {
+        Some(i) if url[i..].starts_with(':') => i,
+        _ => return None,
+    };
+    let scheme = &url[..end];
+    let valid = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.');
+    if valid {
+        Some(scheme.to_ascii_lowercase())
+    } else {
+        None
+    }
+}
+
+/// Whether `value` is what the `attr` attribute of an HTML `tag` element
+/// defaults to, for `drop_default_attributes`.
+fn is_default_attr_value(tag: &LocalName, attr: &QualName, value: &str) -> bool {
+    if attr.ns != ns!() {
+        return false;
+    }
+    let default = match (tag, &attr.local) {
+        (&local_name!("input"), &local_name!("type")) => "text",
+        (&local_name!("button"), &local_name!("type")) => "submit",
+        (&local_name!("form"), &local_name!("method")) => "get",
+        (&local_name!("form"), &local_name!("enctype")) => "application/x-www-form-urlencoded",
+        (&local_name!("script"), &local_name!("type")) => "text/javascript",
+        (&local_name!("style"), &local_name!("type")) => "text/css",
+        (&local_name!("textarea"), &local_name!("wrap")) => "soft",
+        (&local_name!("area"), &local_name!("shape")) => "rect",
+        (&local_name!("td"), &local_name!("colspan"))
+        | (&local_name!("td"), &local_name!("rowspan"))
+        | (&local_name!("th"), &local_name!("colspan"))
+        | (&local_name!("th"), &local_name!("rowspan"))
+        | (&local_name!("col"), &local_name!("span"))
+        | (&local_name!("colgroup"), &local_name!("span")) => "1",
+        _ => return false,
+    };
+    value.eq_ignore_ascii_case(default)
+}
+
+/// Whether `event` starts a `<meta>` that declares the encoding, for
+/// `ensure_charset`.
+fn is_charset_declaration(event: &Event) -> bool {
+    match *event {
+        Event::Start(ref name, ref attrs, _)
+            if name.ns == ns!(html) && name.local == local_name!("meta") =>
+        {
+            attrs.iter().any(|&(ref attr, ref value)| {
+                attr.ns == ns!()
+                    && (attr.local == local_name!("charset")
+                        || (attr.local == local_name!("http-equiv")
+                            && value.eq_ignore_ascii_case("content-type")))
+            })
+        },
+        _ => false,
+    }
+}
+
+/// Attributes whose empty value is kept by `minimize_empty_attributes`.
+fn empty_value_is_significant(name: &QualName) -> bool {
+    name.ns == ns!() && name.local == local_name!("value")
+}
+
+impl<Wr: Write> HtmlSerializer<Wr> {
+    pub fn new(writer: Wr, opts: SerializeOpts) -> Self {
+        let html_name = match opts.traversal_scope {
+            TraversalScope::IncludeNode | TraversalScope::ChildrenOnly(None) => None,
+            TraversalScope::ChildrenOnly(Some(ref n)) => Some(tagname(n)),
+        };
+        let no_indent = html_name
+            .as_ref()
+            .map_or(false, |name| opts.no_indent_children.contains(name));
+        let mut ser = HtmlSerializer {
+            writer: writer,
+            opts: opts,
+            stack: vec![ElemInfo {
+                name: None,
+                preserve_whitespace: html_name.as_ref().map_or(false, preserves_whitespace),
+                html_name: html_name,
+                ignore_children: false,
+                processed_first_child: false,
+                has_block_children: false,
+                no_indent: no_indent,
+                escaped: false,
+                omitted: false,
+                wrapped_text: false,
+                namespaces: vec![],
+            }],
+            pending_whitespace: None,
+            at_start: true,
+            line_pending: false,
+            generated_prefixes: vec![],
+            plaintext_open: None,
+            seen_ids: BTreeSet::new(),
+            head_events: None,
+            head_depth: 0,
+            quirks_mode: None,
+            elements_started: 0,
+            adding_element: false,
+            in_document: false,
+        };
+        let escaped = ser.stack[0]
+            .html_name
+            .as_ref()
+            .map_or(false, |tag| ser.is_escaped(tag));
+        ser.stack[0].escaped = escaped;
+        ser
+    }
+
+    /// Get a reference to the underlying writer.
+    pub fn get_ref(&self) -> &Wr {
+        &self.writer
+    }
+
+    /// Get a mutable reference to the underlying writer. Writing to it directly
+    /// may corrupt the output.
+    pub fn get_mut(&mut self) -> &mut Wr {
+        &mut self.writer
+    }
+
+    /// Unwrap the underlying writer. This doesn't call [`finish`], so with
+    /// `repair_tree` any elements still open are left unclosed.
+    ///
+    /// [`finish`]: #method.finish
+    pub fn into_inner(self) -> Wr {
+        self.writer
+    }
+
+    fn parent(&mut self) -> &mut ElemInfo {
+        if self.stack.len() == 0 {
+            if self.opts.create_missing_parent {
+                warn!("ElemInfo stack empty, creating new parent");
+                self.stack.push(Default::default());
+            } else {
+                panic!("no parent ElemInfo")
+            }
+        }
+        self.stack.last_mut().unwrap()
+    }
+
+    /// Finish serialization. With `repair_tree` set, this writes end tags for any
+    /// elements that are still open; otherwise it does nothing.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.head_events.is_some() {
+            try!(self.flush_head());
+        }
+        try!(self.end_wrapped_text());
+        if self.opts.repair_tree {
+            while self.stack.len() > 1 {
+                let info = self.stack.pop().unwrap();
+                try!(self.write_end_tag(info));
+            }
+        }
+        self.flush_whitespace()
+    }
+
+    fn write_end_tag(&mut self, info: ElemInfo) -> io::Result<()> {
+        if info.ignore_children || info.omitted {
+            return Ok(());
+        }
+
+        // Its end tag would be read as text of the `<plaintext>`.
+        if let Some(open) = self.plaintext_open {
+            if self.stack.len() < open {
+                self.plaintext_open = Some(self.stack.len());
+                return self.flush_whitespace();
+            }
+        }
+
+        let name = info.name.as_ref().expect("end tag for unnamed ElemInfo");
+        let pretty = self.opts.pretty_print && !info.preserve_whitespace && !info.no_indent;
+        if pretty && info.has_block_children {
+            self.pending_whitespace = None;
+            let depth = self.indent_depth();
+            try!(self.write_indent(depth));
+        } else {
+            try!(self.flush_whitespace());
+        }
+        self.line_pending = pretty && !self.is_inline_element(name);
+
+        let tag = self.output_tagname(name);
+        let escape = info.escaped || self.is_escaped(&tag);
+
+        try!(self.write_markup("</", escape));
+        try!(self.write_name(&tag, escape));
+        self.write_markup(">", escape)
+    }
+
+    /// Write what `ensure_charset` held back of the `<head>`, after a
+    /// `<meta charset>` if it has no encoding declaration.
+    fn flush_head(&mut self) -> io::Result<()> {
+        let events = self.head_events.take().unwrap_or_default();
+        self.head_depth = 0;
+        if !events.iter().any(is_charset_declaration) {
+            let meta = QualName::new(None, ns!(html), local_name!("meta"));
+            let charset = QualName::new(None, ns!(), local_name!("charset"));
+            self.adding_element = true;
+            try!(self.start_elem(meta.clone(), vec![(&charset, "utf-8")].into_iter()));
+            try!(self.end_elem(meta));
+        }
+        for event in events.iter() {
+            try!(event.replay(self));
+        }
+        Ok(())
+    }
+
+    /// For `wrap_bare_text`, start a `<p>` before a node that isn't a `block`
+    /// one, directly inside a `<body>`, or end the one that is open before a
+    /// `block` node.
+    fn wrap_bare_text(&mut self, block: bool) -> io::Result<()> {
+        if !self.opts.wrap_bare_text {
+            return Ok(());
+        }
+        if block {
+            return self.end_wrapped_text();
+        }
+        if self.parent().html_name != Some(local_name!("body")) {
+            return Ok(());
+        }
+        let p = QualName::new(None, ns!(html), local_name!("p"));
+        self.adding_element = true;
+        try!(self.start_elem(p, iter::empty()));
+        self.parent().wrapped_text = true;
+        Ok(())
+    }
+
+    /// End the `<p>` started by `wrap_bare_text`, if it is the innermost open
+    /// element.
+    fn end_wrapped_text(&mut self) -> io::Result<()> {
+        if !self.stack.last().map_or(false, |info| info.wrapped_text) {
+            return Ok(());
+        }
+        let info = self.stack.pop().unwrap();
+        self.write_end_tag(info)
+    }
+
+    /// `<br>` and `<wbr>` are always inline, as whitespace around them would
+    /// change what they mean.
+    fn is_inline_element(&self, name: &QualName) -> bool {
+        match name.local {
+            local_name!("br") | local_name!("wbr") if name.ns == ns!(html) => true,
+            _ => name.ns != ns!(html) || self.opts.inline_elements.contains(&name.local),
+        }
+    }
+
+    fn rewrite_tag(&self, name: QualName) -> QualName {
+        if name.ns != ns!(html) {
+            return name;
+        }
+        match self.opts.tag_rewrite.get(&name.local) {
+            Some(local) => QualName::new(name.prefix, name.ns, local.clone()),
+            None => name,
+        }
+    }
+
+    fn output_tagname(&mut self, name: &QualName) -> LocalName {
+        if self.declares_namespaces() && has_unknown_namespace(name, false) {
+            let prefix = self.namespace_prefix(name, true);
+            return LocalName::from(format!("{}:{}", prefix, name.local));
+        }
+        let tag = tagname(name);
+        if self.is_xml_syntax()
+            && name.ns == ns!(html)
+            && tag.chars().any(|c| c.is_ascii_uppercase())
+        {
+            LocalName::from(tag.to_ascii_lowercase())
+        } else {
+            tag
+        }
+    }
+
+    /// Whether namespaces that HTML has no syntax for are declared, with
+    /// `lossless_namespaces` or the XML syntax.
+    fn declares_namespaces(&self) -> bool {
+        self.opts.lossless_namespaces || self.opts.output_syntax == OutputSyntax::Xml
+    }
+
+    /// The prefix to write `name` with in `lossless_namespaces` mode: its own if
+    /// it has one and `own` is set, or else the one generated for its namespace.
+    fn namespace_prefix(&mut self, name: &QualName, own: bool) -> String {
+        match name.prefix {
+            Some(ref prefix) if own && !prefix.is_empty() => return prefix.to_string(),
+            _ => (),
+        }
+        let i = match self.generated_prefixes.iter().position(|ns| *ns == name.ns) {
+            Some(i) => i,
+            None => {
+                self.generated_prefixes.push(name.ns.clone());
+                self.generated_prefixes.len() - 1
+            },
+        };
+        format!("ns{}", i + 1)
+    }
+
+    /// Make sure `name`'s prefix refers to its namespace on the element being
+    /// started, adding to the declarations in `namespaces` if it doesn't yet.
+    /// Returns the prefix.
+    fn bind_prefix(
+        &mut self,
+        name: &QualName,
+        namespaces: &mut Vec<(String, Namespace)>,
+    ) -> String {
+        let mut prefix = self.namespace_prefix(name, true);
+        let clashes = |namespaces: &[(String, Namespace)], prefix: &str| {
+            namespaces
+                .iter()
+                .any(|&(ref p, ref ns)| p == prefix && *ns != name.ns)
+        };
+        if clashes(namespaces, &prefix) {
+            prefix = self.namespace_prefix(name, false);
+        }
+        if namespaces.iter().any(|&(ref p, _)| *p == prefix) {
+            return prefix;
+        }
+        let in_scope = self
+            .stack
+            .iter()
+            .rev()
+            .filter_map(|info| info.namespaces.iter().find(|&&(ref p, _)| *p == prefix))
+            .next();
+        if in_scope.map_or(true, |&(_, ref ns)| *ns != name.ns) {
+            namespaces.push((prefix.clone(), name.ns.clone()));
+        }
+        prefix
+    }
+
+    /// The namespace that an unprefixed element written here is in when the
+    /// output is read as XML: that of the innermost HTML, SVG or MathML element
+    /// written as markup.
+    fn default_namespace(&self) -> Namespace {
+        let ancestor = self
+            .stack
+            .iter()
+            .rev()
+            .filter(|info| !info.escaped)
+            .filter_map(|info| info.name.as_ref())
+            .find(|name| match name.ns {
+                ns!(html) | ns!(svg) | ns!(mathml) => true,
+                _ => false,
+            });
+        match (ancestor, &self.opts.traversal_scope) {
+            (Some(name), _) => name.ns.clone(),
+            (None, &TraversalScope::ChildrenOnly(Some(ref name))) => name.ns.clone(),
+            (None, _) => ns!(html),
+        }
+    }
+
+    /// Whether the output has to be well-formed XML.
+    fn is_xml_syntax(&self) -> bool {
+        self.opts.output_syntax != OutputSyntax::Html
+    }
+
+    fn write_cdata_wrapped(&mut self, text: &str, script: bool) -> io::Result<()> {
+        if text.contains("]]>") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "raw text containing \"]]>\" can't be written as polyglot markup",
+            ));
+        }
+        let (open, close): (&[u8], &[u8]) = if script {
+            (b"//<![CDATA[\n", b"\n//]]>")
+        } else {
+            (b"/*<![CDATA[*/", b"/*]]>*/")
+        };
+        try!(self.writer.write_all(open));
+        try!(self.writer.write_all(text.as_bytes()));
+        self.writer.write_all(close)
+    }
+
+    /// Whether `text` is somewhere a parser wouldn't have put it, for
+    /// `stray_text`.
+    fn is_stray_text(&self, text: &str) -> bool {
+        if text.chars().all(is_collapsible_whitespace) {
+            return false;
+        }
+        match self
+            .stack
+            .last()
+            .and_then(|parent| parent.html_name.as_ref())
+        {
+            Some(&local_name!("html")) | Some(&local_name!("head")) => true,
+            Some(_) => false,
+            None => self.stack.len() == 1 && self.in_document,
+        }
+    }
+
+    /// Write any whitespace held back by `whitespace_text` or `pretty_print`.
+    fn flush_whitespace(&mut self) -> io::Result<()> {
+        let text = match self.pending_whitespace.take() {
+            Some(text) => text,
+            None => return Ok(()),
+        };
+        match self.opts.whitespace_text {
+            WhitespaceText::Preserve => self.writer.write_all(text.as_bytes()),
+            WhitespaceText::SingleSpace => self.writer.write_all(b" "),
+            WhitespaceText::Remove => Ok(()),
+        }
+    }
+
+    /// How many levels `pretty_print` indents a node written now, which is how
+    /// many elements it is in whose tags aren't left out.
+    fn indent_depth(&self) -> usize {
+        self.stack
+            .iter()
+            .skip(1)
+            .filter(|info| !info.omitted)
+            .count()
+    }
+
+    /// Start a new line indented to `depth`.
+    fn write_indent(&mut self, depth: usize) -> io::Result<()> {
+        try!(self.writer.write_all(b"\n"));
+        for _ in 0..depth {
+            try!(self.writer.write_all(self.opts.indent_with.as_bytes()));
+        }
+        Ok(())
+    }
+
+    /// Write the space before an attribute, or with `max_line_length`, the
+    /// line break and alignment in `wrap` before all but the first.
+    fn write_attr_separator(&mut self, wrap: &Option<String>, first: &mut bool) -> io::Result<()> {
+        let separator = match *wrap {
+            Some(ref line) if !*first => line.as_bytes(),
+            _ => b" ",
+        };
+        *first = false;
+        self.writer.write_all(separator)
+    }
+
+    /// Get ready to write a child of the current element: write any whitespace
+    /// held back before it, or with `pretty_print`, the line break and
+    /// indentation it should have instead. Block-level nodes always start a new
+    /// line; inline ones only right after a block-level node.
+    fn begin_node(&mut self, block: bool) -> io::Result<()> {
+        let at_start = self.at_start;
+        let line_pending = self.line_pending;
+        self.at_start = false;
+        self.line_pending = false;
+        if !self.opts.pretty_print || self.parent().preserve_whitespace || self.parent().no_indent {
+            return self.flush_whitespace();
+        }
+        if block {
+            self.parent().has_block_children = true;
+        } else if !line_pending {
+            return self.flush_whitespace();
+        }
+        self.pending_whitespace = None;
+        if at_start {
+            return Ok(());
+        }
+        let depth = self.indent_depth();
+        self.write_indent(depth)
+    }
+
+    /// Whether the current element can't have children, e.g. because it is void,
+    /// so that a child should be dropped. Fails instead with `strict_void` if the
+    /// element is void.
+    fn ignore_child(&mut self) -> io::Result<bool> {
+        if !self.parent().ignore_children {
+            return Ok(false);
+        }
+        let strict_void = self.opts.strict_void;
+        let parent = match self.parent().name {
+            Some(ref name) if strict_void && is_void_element(name) => name.local.to_string(),
+            _ => return Ok(true),
+        };
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("void element <{}> has children", parent),
+        ))
+    }
+
+    fn check_ascii(&self, text: &str) -> io::Result<()> {
+        if !self.opts.ascii_only || !self.opts.strict_ascii {
+            return Ok(());
+        }
+        match text.char_indices().find(|&(_, c)| !c.is_ascii()) {
+            Some((offset, c)) => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                NonAsciiError {
+                    character: c,
+                    offset: offset,
+                },
+            )),
+            None => Ok(()),
+        }
+    }
+
+    /// Write text that is escaped, or in `rcdata`, escaped as `<title>` and
+    /// `<textarea>` text is.
+    fn write_escaped_text(&mut self, text: &str, rcdata: bool) -> io::Result<()> {
+        if rcdata && !self.opts.escape_gt_in_rcdata {
+            for (i, part) in text.split('>').enumerate() {
+                if i > 0 {
+                    try!(self.writer.write_all(b">"));
+                }
+                try!(self.write_escaped(part, false));
+            }
+            Ok(())
+        } else {
+            self.write_escaped(text, false)
+        }
+    }
+
+    /// Whether `source` is a character reference, terminated by a semicolon,
+    /// that is read as `text`.
+    fn is_spelling_of(&self, source: &str, text: &str) -> bool {
+        if !source.starts_with('&') || !source.ends_with(';') || source.len() < 3 {
+            return false;
+        }
+        let name = &source[1..];
+        let decoded = if name.starts_with('#') {
+            let digits = &name[1..name.len() - 1];
+            let (digits, radix) = if digits.starts_with('x') || digits.starts_with('X') {
+                (&digits[1..], 16)
+            } else {
+                (digits, 10)
+            };
+            if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+                return false;
+            }
+            match u32::from_str_radix(digits, radix)
+                .ok()
+                .and_then(::std::char::from_u32)
+            {
+                Some(c) => c.to_string(),
+                None => return false,
+            }
+        } else {
+            if self.is_xml_syntax()
+                || !name[..name.len() - 1]
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric())
+            {
+                return false;
+            }
+            match data::NAMED_ENTITIES.get(name) {
+                Some(&(c1, c2)) => [c1, c2]
+                    .iter()
+                    .filter(|&&c| c != 0)
+                    .filter_map(|&c| ::std::char::from_u32(c))
+                    .collect(),
+                None => return false,
+            }
+        };
+        decoded == text
+    }
 
-    /// Serialize the root node? Default: ChildrenOnly
-    pub traversal_scope: TraversalScope,
+    fn is_escaped(&self, tag: &LocalName) -> bool {
+        match self.opts.allowed_tags {
+            Some(ref tags) => !tags.contains(tag),
+            None => false,
+        }
+    }
 
-    /// If the serializer is asked to serialize an invalid tree, the default
-    /// behavior is to panic in the event that an `end_elem` is created without a
-    /// matching `start_elem`. Setting this to true will prevent those panics by
-    /// creating a default parent on the element stack. No extra start elem will
-    /// actually be written. Default: false
-    pub create_missing_parent: bool,
-}
+    /// An attribute that the options add to `elem`, replacing any it already has.
+    fn injected_attr(&self, elem: &QualName, attrs: &[AttrRef]) -> Option<(LocalName, String)> {
+        if elem.ns != ns!(html) {
+            return None;
+        }
+        if self.opts.lazy_load_images
+            && (elem.local == local_name!("img") || elem.local == local_name!("iframe"))
+        {
+            let has_loading = attrs
+                .iter()
+                .any(|&(name, _)| name.ns == ns!() && name.local == local_name!("loading"));
+            if has_loading {
+                return None;
+            }
+            return Some((local_name!("loading"), "lazy".to_string()));
+        }
+        match (&elem.local, &self.opts.link_rel, &self.opts.script_nonce) {
+            (&local_name!("a"), &Some(ref rel), _) => Some((local_name!("rel"), rel.clone())),
+            (&local_name!("script"), _, &Some(ref nonce)) => {
+                Some((local_name!("nonce"), nonce.clone()))
+            },
+            (&local_name!("style"), _, &Some(ref nonce)) if self.opts.style_nonce => {
+                Some((local_name!("nonce"), nonce.clone()))
+            },
+            _ => None,
+        }
+    }
 
-impl Default for SerializeOpts {
-    fn default() -> SerializeOpts {
-        SerializeOpts {
-            scripting_enabled: true,
-            traversal_scope: TraversalScope::ChildrenOnly(None),
-            create_missing_parent: false,
+    #[cfg(feature = "unicode-normalization")]
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        use unicode_normalization::{is_nfc, UnicodeNormalization};
+        if self.opts.normalize_nfc && !is_nfc(text) {
+            Cow::Owned(text.nfc().collect())
+        } else {
+            Cow::Borrowed(text)
         }
     }
-}
 
-#[derive(Default)]
-struct ElemInfo {
-    html_name: Option<LocalName>,
-    ignore_children: bool,
-    processed_first_child: bool,
-}
+    #[cfg(not(feature = "unicode-normalization"))]
+    fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        Cow::Borrowed(text)
+    }
 
-pub struct HtmlSerializer<Wr: Write> {
-    pub writer: Wr,
-    opts: SerializeOpts,
-    stack: Vec<ElemInfo>,
-}
+    fn prefix_url(&self, attr: &QualName, value: &str) -> Option<String> {
+        let prefix = match self.opts.src_prefix {
+            Some(ref prefix) if attr.ns == ns!() => prefix,
+            _ => return None,
+        };
+        match attr.local {
+            local_name!("src") | local_name!("href") => (),
+            _ => return None,
+        }
+        let relative = url_scheme(value).is_none()
+            && !value.starts_with("//")
+            && !value.starts_with('#')
+            && !value.is_empty();
+        if !relative {
+            return None;
+        }
+        if prefix.ends_with('/') || value.starts_with('/') {
+            Some(format!("{}{}", prefix, value))
+        } else {
+            Some(format!("{}/{}", prefix, value))
+        }
+    }
 
-fn tagname(name: &QualName) -> LocalName {
-    match name.ns {
-        ns!(html) | ns!(mathml) | ns!(svg) => (),
-        ref ns => {
-            // FIXME(#122)
-            warn!("node with weird namespace {:?}", ns);
-        },
+    fn resolve_url(&self, attr: &QualName, value: &str) -> Option<String> {
+        let base = match self.opts.base_url {
+            Some(ref base) if is_url_attribute(attr) => base,
+            _ => return None,
+        };
+        let resolved = url::resolve(base, value);
+        if resolved.is_none() {
+            warn!("base_url {:?} is not an absolute URL", base);
+        }
+        resolved
     }
 
-    name.local.clone()
-}
+    fn is_url_allowed(&self, attr: &QualName, value: &str) -> bool {
+        let schemes = match self.opts.allowed_url_schemes {
+            Some(ref schemes) if is_url_attribute(attr) => schemes,
+            _ => return true,
+        };
+        url_scheme(value).map_or(true, |scheme| schemes.contains(&scheme))
+    }
 
-impl<Wr: Write> HtmlSerializer<Wr> {
-    pub fn new(writer: Wr, opts: SerializeOpts) -> Self {
-        let html_name = match opts.traversal_scope {
-            TraversalScope::IncludeNode | TraversalScope::ChildrenOnly(None) => None,
-            TraversalScope::ChildrenOnly(Some(ref n)) => Some(tagname(n)),
+    /// A `srcset` without the image candidates whose URLs have a scheme that
+    /// isn't in `allowed_url_schemes`, or `None` if it is unchanged.
+    fn filter_srcset(&self, attr: &QualName, value: &str) -> Option<String> {
+        let schemes = match self.opts.allowed_url_schemes {
+            Some(ref schemes) if attr.ns == ns!() && attr.local == local_name!("srcset") => schemes,
+            _ => return None,
         };
-        HtmlSerializer {
-            writer: writer,
-            opts: opts,
-            stack: vec![ElemInfo {
-                html_name: html_name,
-                ignore_children: false,
-                processed_first_child: false,
-            }],
+        let is_allowed =
+            |url: &str| url_scheme(url).map_or(true, |scheme| schemes.contains(&scheme));
+        match url::filter_srcset(value, is_allowed) {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(srcset) => {
+                warn!("dropping srcset candidates with disallowed URL schemes");
+                Some(srcset)
+            },
         }
     }
 
-    fn parent(&mut self) -> &mut ElemInfo {
-        if self.stack.len() == 0 {
-            if self.opts.create_missing_parent {
-                warn!("ElemInfo stack empty, creating new parent");
-                self.stack.push(Default::default());
-            } else {
-                panic!("no parent ElemInfo")
-            }
+    /// The value to write for an `id` attribute, or `None` to leave it out,
+    /// following `duplicate_ids`.
+    fn unique_id<'a>(&mut self, id: &'a str) -> Option<Cow<'a, str>> {
+        if self.opts.duplicate_ids == DuplicateIds::Keep || id.is_empty() {
+            return Some(Cow::Borrowed(id));
         }
-        self.stack.last_mut().unwrap()
+        if self.seen_ids.insert(id.to_owned()) {
+            return Some(Cow::Borrowed(id));
+        }
+        match self.opts.duplicate_ids {
+            DuplicateIds::Drop => None,
+            _ => {
+                let mut n = 2;
+                while self.seen_ids.contains(&format!("{}-{}", id, n)) {
+                    n += 1;
+                }
+                let suffixed = format!("{}-{}", id, n);
+                self.seen_ids.insert(suffixed.clone());
+                Some(Cow::Owned(suffixed))
+            },
+        }
+    }
+
+    /// `value`, cut to `max_attribute_value_len`, or `None` if the attribute
+    /// should be dropped instead.
+    fn capped_value<'a>(&self, name: &QualName, value: &'a str) -> Option<Cow<'a, str>> {
+        let max = match self.opts.max_attribute_value_len {
+            Some(max) if value.len() > max => max,
+            _ => return Some(Cow::Borrowed(value)),
+        };
+        warn!(
+            "attribute {:?} is longer than the limit of {} bytes",
+            name.local, max
+        );
+        let mut end = max;
+        while !value.is_char_boundary(end) {
+            end -= 1;
+        }
+        match self.opts.long_attribute_values {
+            LongAttributeValue::Truncate => Some(Cow::Borrowed(&value[..end])),
+            LongAttributeValue::TruncateWithEllipsis => {
+                Some(Cow::Owned(format!("{}\u{2026}", &value[..end])))
+            },
+            LongAttributeValue::Drop => None,
+        }
+    }
+
+    fn is_attr_allowed(&self, tag: &LocalName, attr: &QualName) -> bool {
+        if self.opts.strip_event_handlers
+            && attr.local.len() > 2
+            && attr
+                .local
+                .get(..2)
+                .map_or(false, |start| start.eq_ignore_ascii_case("on"))
+        {
+            return false;
+        }
+        let allowed = match self.opts.allowed_attributes {
+            Some(ref allowed) => allowed,
+            None => return true,
+        };
+        // Dropping these would turn a declarative shadow root into a plain
+        // template, or change how it behaves.
+        let shadow_root = *tag == local_name!("template")
+            && attr.ns == ns!()
+            && match attr.local {
+                local_name!("shadowrootmode")
+                | local_name!("shadowrootdelegatesfocus")
+                | local_name!("shadowrootclonable")
+                | local_name!("shadowrootserializable") => true,
+                _ => false,
+            };
+        shadow_root
+            || self.opts.always_allowed_attributes.contains(&attr.local)
+            || allowed
+                .get(tag)
+                .map_or(false, |attrs| attrs.contains(&attr.local))
+    }
+
+    /// Write `markup`, such as the `<` of a start tag, escaped as text if
+    /// `escape` is set.
+    fn write_markup(&mut self, markup: &'static str, escape: bool) -> io::Result<()> {
+        if !escape {
+            return self.writer.write_all(markup.as_bytes());
+        }
+        let mut written = 0;
+        for (i, c) in markup.char_indices() {
+            let reference = match c {
+                '&' => "&amp;",
+                '\u{00A0}' => "&nbsp;",
+                '"' => "&quot;",
+                '<' => "&lt;",
+                '>' => "&gt;",
+                _ => continue,
+            };
+            try!(self.writer.write_all(markup[written..i].as_bytes()));
+            try!(self.writer.write_all(reference.as_bytes()));
+            written = i + c.len_utf8();
+        }
+        self.writer.write_all(markup[written..].as_bytes())
+    }
+
+    /// Write a tag or attribute name. In an escaped tag the name is text, and
+    /// must not be able to start markup of its own.
+    fn write_name(&mut self, name: &str, escape: bool) -> io::Result<()> {
+        try!(self.check_ascii(name));
+        if escape {
+            self.write_escaped(name, false)
+        } else {
+            self.writer.write_all(name.as_bytes())
+        }
+    }
+
+    /// Write `="value"` after an attribute name, escaped as text if `escape`
+    /// is set.
+    fn write_attr_value(&mut self, value: &str, escape: bool) -> io::Result<()> {
+        try!(self.check_ascii(value));
+        try!(self.write_markup("=\"", escape));
+        try!(self.write_escaped(value, !escape));
+        self.write_markup("\"", escape)
     }
 
     fn write_escaped(&mut self, text: &str, attr_mode: bool) -> io::Result<()> {
-        for c in text.chars() {
-            try!(match c {
-                '&' => self.writer.write_all(b"&amp;"),
-                '\u{00A0}' => self.writer.write_all(b"&nbsp;"),
-                '"' if attr_mode => self.writer.write_all(b"&quot;"),
-                '<' if !attr_mode => self.writer.write_all(b"&lt;"),
-                '>' if !attr_mode => self.writer.write_all(b"&gt;"),
-                c => self.writer.write_fmt(format_args!("{}", c)),
+        let style = self.opts.entity_style;
+        let all = style != EntityStyle::Minimal;
+        // Characters that don't need escaping are written a run at a time.
+        let mut written = 0;
+        for (i, c) in text.char_indices() {
+            // `None` for a numeric reference.
+            let reference = match c {
+                '&' if self.opts.minimal_amp_escaping
+                    && !self.is_xml_syntax()
+                    && !starts_character_reference(&text[i + 1..], attr_mode) =>
+                {
+                    continue
+                },
+                '&' | '"' | '\'' | '<' | '>' if style == EntityStyle::Numeric => None,
+                '&' => Some("&amp;"),
+                '\u{00A0}'
+                    if self.opts.escape_nbsp
+                        && (self.is_xml_syntax() || style == EntityStyle::Numeric) =>
+                {
+                    None
+                },
+                '\u{00A0}' if self.opts.escape_nbsp => Some("&nbsp;"),
+                '"' if attr_mode || all => Some("&quot;"),
+                '\'' if all => Some("&apos;"),
+                '<' if !attr_mode || all => Some("&lt;"),
+                '>' if !attr_mode || all => Some("&gt;"),
+                c if is_bidi_control(c) && self.opts.bidi_controls == BidiControls::Strip => {
+                    Some("")
+                },
+                c if (self.opts.ascii_only && !c.is_ascii())
+                    || self
+                        .opts
+                        .numeric_reference_ranges
+                        .iter()
+                        .any(|range| range.contains(&(c as u32)))
+                    || (is_bidi_control(c) && self.opts.bidi_controls == BidiControls::Escape) =>
+                {
+                    None
+                },
+                _ => continue,
+            };
+            try!(self.writer.write_all(text[written..i].as_bytes()));
+            written = i + c.len_utf8();
+            try!(match reference {
+                Some(reference) => self.writer.write_all(reference.as_bytes()),
+                None => self.write_hex_reference(c),
             });
         }
-        Ok(())
+        self.writer.write_all(text[written..].as_bytes())
+    }
+
+    fn write_hex_reference(&mut self, c: char) -> io::Result<()> {
+        if self.opts.hex_uppercase {
+            self.writer.write_fmt(format_args!("&#x{:X};", c as u32))
+        } else {
+            self.writer.write_fmt(format_args!("&#x{:x};", c as u32))
+        }
     }
 }
 
-lazy_static! {
-    static ref ALLOWED_TAGS: HashSet<&'static str> = {
-        let mut s = HashSet::new();
-        s.insert("p");
-        s.insert("br");
-        s.insert("strong");
-        s.insert("em");
-        s.insert("del");
-        s.insert("blockquote");
-        s.insert("code");
-        s.insert("pre");
-        s.insert("h1");
-        s.insert("h2");
-        s.insert("h3");
-        s.insert("h4");
-        s.insert("h5");
-        s.insert("h6");
-        s.insert("a");
-        s.insert("ul");
-        s.insert("ol");
-        s.insert("li");
-        s.insert("hr");
-        s
-    };
+/// Whether a parser would read an `&` followed by `rest` as the start of a
+/// character reference, or report an ambiguous ampersand. In attribute values,
+/// a named reference without a semicolon isn't read as one if it is followed by
+/// an alphanumeric or `=`.
+fn starts_character_reference(rest: &str, attr_mode: bool) -> bool {
+    if rest.starts_with('#') {
+        let digits = &rest[1..];
+        return match digits.chars().next() {
+            Some('x') | Some('X') => digits[1..].starts_with(|c: char| c.is_ascii_hexdigit()),
+            _ => digits.starts_with(|c: char| c.is_ascii_digit()),
+        };
+    }
+    let name_len = rest
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(rest.len());
+    if name_len == 0 {
+        return false;
+    }
+    if rest[name_len..].starts_with(';') {
+        return true;
+    }
+    // The longest reference without a semicolon that the name starts with.
+    let legacy = (1..name_len + 1).rev().find(|&len| {
+        data::NAMED_ENTITIES
+            .get(&rest[..len])
+            .map_or(false, |&(c, _)| c != 0)
+    });
+    match legacy {
+        Some(len) if attr_mode => {
+            !rest[len..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '=')
+        },
+        Some(_) => true,
+        None => false,
+    }
 }
 
-fn escape_text(text: &'static str, should_escape: bool) -> String {
-    if !should_escape {
-        return text.to_owned();
+/// Make comment text safe to write between `<!--` and `-->`. Besides `--`,
+/// this breaks up `<!-`, which a parser takes for the start of a nested comment,
+/// and which would otherwise run into the closing `-->` at the end of the text.
+fn escape_comment<'a>(text: &'a str) -> Cow<'a, str> {
+    if !text.contains("--")
+        && !text.contains("<!-")
+        && !text.starts_with('>')
+        && !text.starts_with("->")
+    {
+        return Cow::Borrowed(text);
     }
 
-    let mut result = String::new();
+    let mut result = String::with_capacity(text.len() + 2);
+    if text.starts_with('>') || text.starts_with("->") {
+        result.push(' ');
+    }
     for c in text.chars() {
-        match c {
-            '&' => result += "&amp;",
-            '\u{00A0}' => result += "&nbsp;",
-            '"' => result += "&quot;",
-            '<' => result += "&lt;",
-            '>' => result += "&gt;",
-            c => result += &c.to_string(),
-        };
+        if c == '-' && (result.ends_with('-') || result.ends_with("<!")) {
+            result.push(' ');
+        }
+        result.push(c);
     }
-
-    result
+    Cow::Owned(result)
 }
 
 impl<Wr: Write> Serializer for HtmlSerializer<Wr> {
@@ -169,27 +2350,294 @@ impl<Wr: Write> Serializer for HtmlSerializer<Wr> {
     where
         AttrIter: Iterator<Item = AttrRef<'a>>,
     {
+        self.start_elem_as_written(name, attrs, false)
+    }
+
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        if let Some(ref mut events) = self.head_events {
+            events.push(Event::start(name, attrs, self_closing));
+            self.head_depth += 1;
+            return Ok(());
+        }
+        if !mem::replace(&mut self.adding_element, false) {
+            self.elements_started += 1;
+        }
+        let inline = self.is_inline_element(&name);
+        try!(self.wrap_bare_text(!inline));
+        if let Some(max) = self.opts.max_total_elements {
+            if self.elements_started > max {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("more than {} elements", max),
+                ));
+            }
+        }
+        if self.stack.len() == 1 && name.ns == ns!(html) && name.local == local_name!("html") {
+            self.in_document = true;
+        }
+        let name = self.rewrite_tag(name);
         let html_name = match name.ns {
             ns!(html) => Some(name.local.clone()),
             _ => None,
         };
 
-        if self.parent().ignore_children {
+        if try!(self.ignore_child()) {
             self.stack.push(ElemInfo {
+                name: Some(name),
                 html_name: html_name,
                 ignore_children: true,
                 processed_first_child: false,
+                preserve_whitespace: false,
+                has_block_children: false,
+                no_indent: false,
+                escaped: false,
+                omitted: false,
+                wrapped_text: false,
+                namespaces: vec![],
             });
             return Ok(());
         }
+        if self.opts.omit_html_body_tags && is_html_body_tag(&name) {
+            let info = ElemInfo {
+                name: Some(name),
+                html_name: html_name,
+                preserve_whitespace: self.parent().preserve_whitespace,
+                no_indent: self.parent().no_indent,
+                omitted: true,
+                ..Default::default()
+            };
+            self.stack.push(info);
+            return Ok(());
+        }
+        let block = !self.is_inline_element(&name);
+        // The indentation of the line the tag will start, if it starts one.
+        let line_indent = if block
+            && self.opts.pretty_print
+            && self.opts.max_line_length.is_some()
+            && !self.parent().preserve_whitespace
+            && !self.parent().no_indent
+        {
+            let depth = if self.at_start {
+                0
+            } else {
+                self.indent_depth()
+            };
+            Some(self.opts.indent_with.repeat(depth))
+        } else {
+            None
+        };
+        try!(self.begin_node(block));
 
-        let tag = tagname(&name);
-        let escape = !ALLOWED_TAGS.contains(&*tag.to_owned());
+        let tag = self.output_tagname(&name);
+        let attrs: Vec<AttrRef> = attrs.collect();
+        let hooked = self.opts.element_hook.as_ref().map(|hook| {
+            let mut owned = attrs
+                .iter()
+                .map(|&(name, value)| (name.clone(), value.to_owned()))
+                .collect();
+            hook.call(&name, &mut owned);
+            owned
+        });
+        let mut attrs: Vec<AttrRef> = match hooked {
+            Some(ref hooked) => hooked
+                .iter()
+                .map(|&(ref name, ref value)| (name, &**value))
+                .collect(),
+            None => attrs,
+        };
+        if !self.opts.attribute_order_priority.is_empty() {
+            let priority = &self.opts.attribute_order_priority;
+            // A stable sort, which keeps the rest in their order.
+            attrs.sort_by_key(|&(name, _)| match name.ns {
+                ns!() => priority
+                    .iter()
+                    .position(|local| *local == name.local)
+                    .unwrap_or(priority.len()),
+                _ => priority.len(),
+            });
+        }
+        let escape = self.is_escaped(&tag)
+            || !is_custom_element_allowed(&self.opts, &attrs)
+            || !is_script_allowed(&self.opts, &name, &attrs);
+        if escape && self.opts.disallowed_replacement.is_some() {
+            let replacement = self.opts.disallowed_replacement.clone().unwrap();
+            try!(self.check_ascii(&replacement));
+            try!(self.writer.write_all(replacement.as_bytes()));
+            self.parent().processed_first_child = true;
+            self.stack.push(ElemInfo {
+                name: Some(name),
+                html_name: html_name,
+                ignore_children: true,
+                processed_first_child: false,
+                preserve_whitespace: false,
+                has_block_children: false,
+                no_indent: false,
+                escaped: true,
+                omitted: false,
+                wrapped_text: false,
+                namespaces: vec![],
+            });
+            return Ok(());
+        }
+        let tag_is_html = name.ns == ns!(html);
+        let wrap = match (self.opts.max_line_length, line_indent) {
+            (Some(max), Some(ref indent)) if !escape => {
+                let width = attrs
+                    .iter()
+                    .filter(|&&(attr, _)| self.is_attr_allowed(&tag, attr))
+                    .fold(
+                        indent.chars().count() + tag.len() + 2,
+                        |width, &(attr, value)| {
+                            width + attr.local.len() + value.chars().count() + 4
+                        },
+                    );
+                if width > max {
+                    Some(format!("\n{}{}", indent, " ".repeat(tag.len() + 2)))
+                } else {
+                    None
+                }
+            },
+            _ => None,
+        };
+        let mut first_attr = true;
 
-        try!(self.writer.write_all(escape_text("<", escape).as_bytes()));
-        try!(self.writer.write_all(tag.as_bytes()));
+        try!(self.write_markup("<", escape));
+        try!(self.write_name(&tag, escape));
+        if self.is_xml_syntax() {
+            let declared = attrs.iter().any(|&(name, _)| {
+                name.local == local_name!("xmlns") && (name.ns == ns!() || name.ns == ns!(xmlns))
+            });
+            let xmlns = match (&name.ns, &name.local) {
+                (&ns!(html), &local_name!("html")) => Some(ns!(html)),
+                (&ns!(svg), &local_name!("svg")) => Some(ns!(svg)),
+                (&ns!(mathml), &local_name!("math")) => Some(ns!(mathml)),
+                // E.g. HTML inside an SVG `<foreignObject>`.
+                (&ns!(html), _) | (&ns!(svg), _) | (&ns!(mathml), _)
+                    if name.ns != self.default_namespace() =>
+                {
+                    Some(name.ns.clone())
+                },
+                _ => None,
+            };
+            if let (Some(ns), false) = (xmlns, declared) {
+                try!(self.write_attr_separator(&wrap, &mut first_attr));
+                try!(self.write_markup("xmlns=\"", escape));
+                try!(self.writer.write_all(ns.as_bytes()));
+                try!(self.write_markup("\"", escape));
+            }
+        }
+        let mut namespaces = vec![];
+        if self.declares_namespaces() && has_unknown_namespace(&name, false) {
+            self.bind_prefix(&name, &mut namespaces);
+        }
+        let mut declared = vec![];
+        let injected = self.injected_attr(&name, &attrs);
+        let mut written = 0;
+        let has_xml_lang = attrs
+            .iter()
+            .any(|&(name, _)| name.ns == ns!(xml) && name.local == local_name!("lang"));
+        let mut mirrored_lang = None;
         for (name, value) in attrs {
-            try!(self.writer.write_all(b" "));
+            let renamed = self.opts.attr_name_map.get(name).cloned();
+            let name = renamed.as_ref().unwrap_or(name);
+            let prefixed = self.prefix_url(name, value);
+            let value = prefixed.as_ref().map_or(value, |url| &**url);
+            let resolved = self.resolve_url(name, value);
+            let value = resolved.as_ref().map_or(value, |url| &**url);
+            let normalized = self.normalize(value);
+            let value = &*normalized;
+            let capped = match self.capped_value(name, value) {
+                Some(capped) => capped,
+                None => continue,
+            };
+            let value = &*capped;
+            let lowercased =
+                if name.ns == ns!() && self.opts.lowercase_value_attrs.contains(&name.local) {
+                    Some(value.to_ascii_lowercase())
+                } else {
+                    None
+                };
+            let value = lowercased.as_ref().map_or(value, |value| &**value);
+            let canonical =
+                if name.ns == ns!() && self.opts.canonicalize_token_attrs.contains(&name.local) {
+                    let tokens: BTreeSet<&str> = value
+                        .split(|c: char| c.is_ascii_whitespace())
+                        .filter(|token| !token.is_empty())
+                        .collect();
+                    Some(tokens.into_iter().collect::<Vec<_>>().join(" "))
+                } else {
+                    None
+                };
+            let value = canonical.as_ref().map_or(value, |value| &**value);
+            if !self.is_attr_allowed(&tag, name) || !self.is_url_allowed(name, value) {
+                continue;
+            }
+            let filtered = self.filter_srcset(name, value);
+            let value = filtered.as_ref().map_or(value, |srcset| &**srcset);
+            if filtered
+                .as_ref()
+                .map_or(false, |srcset| srcset.trim().is_empty())
+            {
+                continue;
+            }
+            let sanitized = match self.opts.style_sanitizer {
+                Some(ref sanitizer) if name.ns == ns!() && name.local == local_name!("style") => {
+                    Some(sanitizer.sanitize(value))
+                },
+                _ => None,
+            };
+            let value = sanitized.as_ref().map_or(value, |style| &**style);
+            if sanitized
+                .as_ref()
+                .map_or(false, |style| style.trim().is_empty())
+            {
+                continue;
+            }
+            if self.opts.drop_default_attributes
+                && tag_is_html
+                && is_default_attr_value(&tag, name, value)
+            {
+                continue;
+            }
+            if let Some((ref injected, _)) = injected {
+                if name.ns == ns!() && name.local == *injected {
+                    continue;
+                }
+            }
+            if self.opts.max_attributes_per_element == Some(written) {
+                warn!(
+                    "dropping attributes past the limit of {} on {:?}",
+                    written, tag
+                );
+                break;
+            }
+            let unique = if !escape && name.ns == ns!() && name.local == local_name!("id") {
+                match self.unique_id(value) {
+                    Some(id) => Some(id),
+                    None => continue,
+                }
+            } else {
+                None
+            };
+            let value = unique.as_ref().map_or(value, |id| &**id);
+            written += 1;
+            if self.opts.mirror_lang_to_xml
+                && !has_xml_lang
+                && name.ns == ns!()
+                && name.local == local_name!("lang")
+            {
+                mirrored_lang = Some(value.to_owned());
+            }
+
+            try!(self.write_attr_separator(&wrap, &mut first_attr));
 
             match name.ns {
                 ns!() => (),
@@ -197,117 +2645,365 @@ impl<Wr: Write> Serializer for HtmlSerializer<Wr> {
                 ns!(xmlns) => {
                     if name.local != local_name!("xmlns") {
                         try!(self.writer.write_all(b"xmlns:"));
+                        declared.push((name.local.to_string(), Namespace::from(value)));
                     }
-                }
+                },
+                ns!(xlink) if self.opts.output_syntax == OutputSyntax::Xml => {
+                    let xlink = QualName::new(
+                        Some(namespace_prefix!("xlink")),
+                        ns!(xlink),
+                        name.local.clone(),
+                    );
+                    let prefix = self.bind_prefix(&xlink, &mut namespaces);
+                    try!(self.write_name(&prefix, escape));
+                    try!(self.writer.write_all(b":"));
+                },
                 ns!(xlink) => try!(self.writer.write_all(b"xlink:")),
+                _ if self.declares_namespaces() => {
+                    let prefix = self.bind_prefix(name, &mut namespaces);
+                    try!(self.write_name(&prefix, escape));
+                    try!(self.writer.write_all(b":"));
+                },
                 ref ns => {
                     // FIXME(#122)
                     warn!("attr with weird namespace {:?}", ns);
                     try!(self.writer.write_all(b"unknown_namespace:"));
-                }
+                },
             }
 
-            try!(self.writer.write_all(name.local.as_bytes()));
-            try!(self.writer.write_all(escape_text("=\"", escape).as_bytes()));
-            try!(self.write_escaped(value, true));
-            try!(self.writer.write_all(escape_text("\"", escape).as_bytes()));
-        }
-        try!(self.writer.write_all(escape_text(">", escape).as_bytes()));
-
-        let ignore_children = name.ns == ns!(html)
-            && match name.local {
-                local_name!("area")
-                | local_name!("base")
-                | local_name!("basefont")
-                | local_name!("bgsound")
-                | local_name!("br")
-                | local_name!("col")
-                | local_name!("embed")
-                | local_name!("frame")
-                | local_name!("hr")
-                | local_name!("img")
-                | local_name!("input")
-                | local_name!("keygen")
-                | local_name!("link")
-                | local_name!("meta")
-                | local_name!("param")
-                | local_name!("source")
-                | local_name!("track")
-                | local_name!("wbr") => true,
-                _ => false,
-            };
+            if self.is_xml_syntax() && name.ns == ns!() && tag_is_html {
+                try!(self.write_name(&name.local.to_ascii_lowercase(), escape));
+            } else {
+                try!(self.write_name(&name.local, escape));
+            }
+            if self.opts.minimize_empty_attributes
+                && !self.is_xml_syntax()
+                && value.is_empty()
+                && !empty_value_is_significant(name)
+            {
+                continue;
+            }
+            try!(self.write_attr_value(value, escape));
+        }
+        for &(ref prefix, ref ns) in namespaces.iter() {
+            if declared
+                .iter()
+                .any(|&(ref p, ref n)| p == prefix && n == ns)
+            {
+                continue;
+            }
+            try!(self.write_attr_separator(&wrap, &mut first_attr));
+            try!(self.writer.write_all(b"xmlns:"));
+            try!(self.write_name(prefix, escape));
+            try!(self.write_attr_value(ns, escape));
+        }
+        if let Some(lang) = mirrored_lang {
+            try!(self.write_attr_separator(&wrap, &mut first_attr));
+            try!(self.write_markup("xml:lang", escape));
+            try!(self.write_attr_value(&lang, escape));
+        }
+        if let Some((attr, value)) = injected {
+            try!(self.write_attr_separator(&wrap, &mut first_attr));
+            try!(self.write_name(&attr, escape));
+            try!(self.write_attr_value(&value, escape));
+        }
+
+        let ignore_children = is_void_element(&name);
+        if self.is_xml_syntax() && ignore_children {
+            try!(self.write_markup(" />", escape));
+        } else if self.opts.preserve_self_closing && self_closing && ignore_children {
+            try!(self.write_markup("/>", escape));
+        } else {
+            try!(self.write_markup(">", escape));
+        }
 
         self.parent().processed_first_child = true;
 
+        let preserve_whitespace = self.parent().preserve_whitespace
+            || html_name.as_ref().map_or(false, preserves_whitespace);
+        let no_indent = self.parent().no_indent
+            || html_name
+                .as_ref()
+                .map_or(false, |name| self.opts.no_indent_children.contains(name));
+        let hold_back_head = self.opts.ensure_charset
+            && tag_is_html
+            && name.local == local_name!("head")
+            && !escape
+            && self.stack.len() == 2
+            && self.stack[1].html_name == Some(local_name!("html"));
+        if ignore_children {
+            // Void elements have no end tag to start the next line after.
+            self.line_pending =
+                self.opts.pretty_print && block && !preserve_whitespace && !no_indent;
+        }
         self.stack.push(ElemInfo {
+            name: Some(name),
             html_name: html_name,
             ignore_children: ignore_children,
             processed_first_child: false,
+            preserve_whitespace: preserve_whitespace,
+            has_block_children: false,
+            no_indent: no_indent,
+            escaped: escape,
+            omitted: false,
+            wrapped_text: false,
+            namespaces: namespaces,
         });
+        if tag_is_html
+            && tag == local_name!("plaintext")
+            && !escape
+            && !self.is_xml_syntax()
+            && self.plaintext_open.is_none()
+        {
+            self.plaintext_open = Some(self.stack.len());
+        }
+        if hold_back_head {
+            self.head_events = Some(vec![]);
+        }
 
         Ok(())
     }
 
     fn end_elem(&mut self, name: QualName) -> io::Result<()> {
-        let info = match self.stack.pop() {
+        if let Some(ref mut events) = self.head_events {
+            if self.head_depth > 0 {
+                events.push(Event::End(name));
+                self.head_depth -= 1;
+                return Ok(());
+            }
+        }
+        if self.head_events.is_some() {
+            try!(self.flush_head());
+        }
+        try!(self.end_wrapped_text());
+        let name = self.rewrite_tag(name);
+        if self.opts.repair_tree {
+            let open = self
+                .stack
+                .iter()
+                .rposition(|info| info.name.as_ref() == Some(&name));
+            match open {
+                Some(i) => {
+                    while self.stack.len() > i + 1 {
+                        let info = self.stack.pop().unwrap();
+                        try!(self.write_end_tag(info));
+                    }
+                },
+                None => {
+                    warn!("dropping unmatched end tag {:?}", name);
+                    return Ok(());
+                },
+            }
+        }
+
+        let mut info = match self.stack.pop() {
             Some(info) => info,
             None if self.opts.create_missing_parent => {
                 warn!("missing ElemInfo, creating default.");
                 Default::default()
-            }
+            },
             _ => panic!("no ElemInfo"),
         };
-        if info.ignore_children {
+        info.name = Some(name);
+        self.write_end_tag(info)
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_text_with_entities(text, &[])
+    }
+
+    /// Character references are written as they were in escaped text, unless
+    /// they would be read back as something else. Named ones are left out in
+    /// the XHTML and XML syntaxes, as XML doesn't have them.
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        if let Some(ref mut events) = self.head_events {
+            events.push(Event::text(text, entities));
+            return Ok(());
+        }
+        if try!(self.ignore_child()) {
+            return Ok(());
+        }
+        let pretty = self.opts.pretty_print && !self.parent().no_indent;
+        if (self.opts.whitespace_text != WhitespaceText::Preserve || pretty)
+            && !self.parent().preserve_whitespace
+            && !text.is_empty()
+            && text.chars().all(is_collapsible_whitespace)
+        {
+            self.pending_whitespace
+                .get_or_insert_with(String::new)
+                .push_str(text);
             return Ok(());
         }
+        if self.opts.stray_text != StrayText::Keep && self.is_stray_text(text) {
+            match self.opts.stray_text {
+                StrayText::Error => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("stray text {:?}", text),
+                    ))
+                },
+                StrayText::Drop => {
+                    warn!("dropping stray text {:?}", text);
+                    return Ok(());
+                },
+                _ => warn!("stray text {:?}", text),
+            }
+        }
+        if !text.chars().all(is_collapsible_whitespace) {
+            try!(self.wrap_bare_text(false));
+        }
+        try!(self.begin_node(false));
+        try!(self.check_ascii(text));
+        let parent_tag = self.parent().html_name.clone();
+        let mode = parent_tag.as_ref().map_or(TextMode::Normal, html_text_mode);
+        let escape = match parent_tag {
+            Some(local_name!("noscript")) => self
+                .opts
+                .serialize_noscript_as_elements
+                .unwrap_or(!self.opts.scripting_enabled),
 
-        let tag = tagname(&name);
-        let escape = !ALLOWED_TAGS.contains(&*tag.to_owned());
+            Some(ref tag) => {
+                mode != TextMode::RawText && !self.opts.extra_raw_text_elements.contains(tag)
+            },
 
-        try!(self.writer.write_all(escape_text("</", escape).as_bytes()));
-        try!(self.writer.write_all(tag.as_bytes()));
-        self.writer.write_all(escape_text(">", escape).as_bytes())
-    }
+            None => true,
+        };
+        // The text of an escaped raw text element isn't raw any more, and XML
+        // has no raw text.
+        let escape =
+            escape || self.parent().escaped || self.opts.output_syntax == OutputSyntax::Xml;
+        // After a `<plaintext>`, everything is written as a parser would read it
+        // back, as the raw text of the `<plaintext>`.
+        let plaintext = self.plaintext_open.is_some();
+        let escape = escape && !plaintext;
 
-    fn write_text(&mut self, text: &str) -> io::Result<()> {
-        let escape = match self.parent().html_name {
-            Some(local_name!("style"))
-            | Some(local_name!("script"))
-            | Some(local_name!("xmp"))
-            | Some(local_name!("iframe"))
-            | Some(local_name!("noembed"))
-            | Some(local_name!("noframes"))
-            | Some(local_name!("plaintext")) => false,
-
-            Some(local_name!("noscript")) => !self.opts.scripting_enabled,
-
-            _ => true,
+        let rcdata = mode == TextMode::Rcdata && !plaintext;
+
+        let cdata = match parent_tag {
+            Some(local_name!("script")) => Some(true),
+            Some(local_name!("style")) => Some(false),
+            _ => None,
         };
 
-        if escape {
-            self.write_escaped(text, false)
+        if escape || rcdata {
+            let normalized = self.normalize(text);
+            // The spans are offsets into the text as it was.
+            let entities = match normalized {
+                Cow::Borrowed(_) => entities,
+                Cow::Owned(_) => &[],
+            };
+            let text = &*normalized;
+            let mut written = 0;
+            for entity in entities {
+                let range = entity.range.clone();
+                if range.start < written
+                    || range.end > text.len()
+                    || !text.is_char_boundary(range.start)
+                    || !text.is_char_boundary(range.end)
+                    || !self.is_spelling_of(entity.source, &text[range.clone()])
+                {
+                    continue;
+                }
+                try!(self.write_escaped_text(&text[written..range.start], rcdata));
+                try!(self.writer.write_all(entity.source.as_bytes()));
+                written = range.end;
+            }
+            self.write_escaped_text(&text[written..], rcdata)
+        } else if self.opts.output_syntax == OutputSyntax::Xhtml
+            && cdata.is_some()
+            && (text.contains('<') || text.contains('&'))
+        {
+            self.write_cdata_wrapped(text, cdata.unwrap())
         } else {
             self.writer.write_all(text.as_bytes())
         }
     }
 
     fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        if let Some(ref mut events) = self.head_events {
+            events.push(Event::Comment(text.to_owned()));
+            return Ok(());
+        }
+        if try!(self.ignore_child()) {
+            return Ok(());
+        }
+        let marker = self.opts.editor_markers.contains(text);
+        if let Some(ref preserved) = self.opts.preserved_comment_exact {
+            if !marker && !preserved.contains(text.trim()) {
+                return Ok(());
+            }
+        }
+        try!(self.begin_node(!marker));
+        self.line_pending = !marker;
+        let text = if self.opts.preserve_raw_comments {
+            Cow::Borrowed(text)
+        } else {
+            escape_comment(text)
+        };
+        try!(self.check_ascii(&text));
         try!(self.writer.write_all(b"<!--"));
         try!(self.writer.write_all(text.as_bytes()));
         self.writer.write_all(b"-->")
     }
 
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        if raw.contains('>') {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "raw doctype contains \">\"",
+            ));
+        }
+        self.write_doctype(raw)
+    }
+
     fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        if let Some(ref mut events) = self.head_events {
+            events.push(Event::Doctype(name.to_owned()));
+            return Ok(());
+        }
+        if self.opts.doctype_only_in_no_quirks {
+            match self.quirks_mode {
+                Some(NoQuirks) | None => (),
+                Some(mode) => {
+                    warn!("leaving out the doctype of a document in {:?} mode", mode);
+                    return Ok(());
+                },
+            }
+        }
+        if self.stack.len() == 1 {
+            self.in_document = true;
+        }
+        try!(self.begin_node(true));
+        self.line_pending = true;
+        try!(self.check_ascii(name));
         try!(self.writer.write_all(b"<!DOCTYPE "));
         try!(self.writer.write_all(name.as_bytes()));
         self.writer.write_all(b">")
     }
 
     fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        if let Some(ref mut events) = self.head_events {
+            events.push(Event::ProcessingInstruction(
+                target.to_owned(),
+                data.to_owned(),
+            ));
+            return Ok(());
+        }
+        if try!(self.ignore_child()) {
+            return Ok(());
+        }
+        try!(self.begin_node(false));
+        try!(self.check_ascii(target));
+        try!(self.check_ascii(data));
         try!(self.writer.write_all(b"<?"));
         try!(self.writer.write_all(target.as_bytes()));
         try!(self.writer.write_all(b" "));
         try!(self.writer.write_all(data.as_bytes()));
         self.writer.write_all(b">")
     }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.quirks_mode = Some(mode);
+        self.in_document = true;
+    }
 }
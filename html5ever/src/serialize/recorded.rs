@@ -0,0 +1,72 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io;
+use std::ops::Range;
+
+use super::{AttrRef, EntitySpan, Serializer};
+use QualName;
+
+/// A call to a `Serializer`, kept to be passed on later by `replay`, for
+/// serializers that need to see what comes next before writing.
+pub enum Event {
+    Start(QualName, Vec<(QualName, String)>, bool),
+    End(QualName),
+    Text(String, Vec<(Range<usize>, String)>),
+    Comment(String),
+    Doctype(String),
+    DoctypeRaw(String),
+    ProcessingInstruction(String, String),
+}
+
+impl Event {
+    pub fn start<'a, AttrIter>(name: QualName, attrs: AttrIter, self_closing: bool) -> Event
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        let attrs = attrs
+            .map(|(name, value)| (name.clone(), value.to_owned()))
+            .collect();
+        Event::Start(name, attrs, self_closing)
+    }
+
+    pub fn text(text: &str, entities: &[EntitySpan]) -> Event {
+        let entities = entities
+            .iter()
+            .map(|entity| (entity.range.clone(), entity.source.to_owned()))
+            .collect();
+        Event::Text(text.to_owned(), entities)
+    }
+
+    pub fn replay<S: Serializer>(&self, serializer: &mut S) -> io::Result<()> {
+        match *self {
+            Event::Start(ref name, ref attrs, self_closing) => {
+                let attrs = attrs.iter().map(|&(ref name, ref value)| (name, &**value));
+                serializer.start_elem_as_written(name.clone(), attrs, self_closing)
+            },
+            Event::End(ref name) => serializer.end_elem(name.clone()),
+            Event::Text(ref text, ref entities) => {
+                let entities: Vec<EntitySpan> = entities
+                    .iter()
+                    .map(|&(ref range, ref source)| EntitySpan {
+                        range: range.clone(),
+                        source: source,
+                    })
+                    .collect();
+                serializer.write_text_with_entities(text, &entities)
+            },
+            Event::Comment(ref text) => serializer.write_comment(text),
+            Event::Doctype(ref name) => serializer.write_doctype(name),
+            Event::DoctypeRaw(ref raw) => serializer.write_doctype_raw(raw),
+            Event::ProcessingInstruction(ref target, ref data) => {
+                serializer.write_processing_instruction(target, data)
+            },
+        }
+    }
+}
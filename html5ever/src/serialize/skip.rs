@@ -0,0 +1,156 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, Write};
+
+use super::{AttrRef, EntitySpan, HtmlSerializer, Serialize, SerializeOpts, Serializer};
+use interface::QuirksMode;
+use QualName;
+
+/// Serialize `node` like [`serialize`], leaving out every element for which
+/// `predicate` returns true, along with everything inside it.
+///
+/// [`serialize`]: fn.serialize.html
+pub fn serialize_skipping<Wr, T, F>(
+    writer: Wr,
+    node: &T,
+    opts: SerializeOpts,
+    predicate: F,
+) -> io::Result<()>
+where
+    Wr: Write,
+    T: Serialize,
+    F: FnMut(&QualName, &[AttrRef]) -> bool,
+{
+    let mut ser = SkipSerializer::new(HtmlSerializer::new(writer, opts.clone()), predicate);
+    try!(node.serialize(&mut ser, opts.traversal_scope));
+    ser.into_inner().finish()
+}
+
+/// A `Serializer` that passes everything on to another one, except the elements
+/// for which a predicate returns true. Those are dropped together with their
+/// contents.
+pub struct SkipSerializer<S, F> {
+    inner: S,
+    predicate: F,
+    /// How many elements are open inside the outermost skipped one, counting
+    /// itself; zero when nothing is being skipped.
+    skip_depth: usize,
+}
+
+impl<S, F> SkipSerializer<S, F>
+where
+    S: Serializer,
+    F: FnMut(&QualName, &[AttrRef]) -> bool,
+{
+    /// Wrap `inner`, skipping the elements `predicate` selects. The predicate is
+    /// called with each element's name and attributes.
+    pub fn new(inner: S, predicate: F) -> Self {
+        SkipSerializer {
+            inner: inner,
+            predicate: predicate,
+            skip_depth: 0,
+        }
+    }
+
+    /// Unwrap the inner serializer.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, F> Serializer for SkipSerializer<S, F>
+where
+    S: Serializer,
+    F: FnMut(&QualName, &[AttrRef]) -> bool,
+{
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.start_elem_as_written(name, attrs, false)
+    }
+
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        if self.skip_depth > 0 {
+            self.skip_depth += 1;
+            return Ok(());
+        }
+
+        let attrs: Vec<AttrRef> = attrs.collect();
+        if (self.predicate)(&name, &attrs) {
+            self.skip_depth = 1;
+            return Ok(());
+        }
+        self.inner
+            .start_elem_as_written(name, attrs.into_iter(), self_closing)
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        if self.skip_depth > 0 {
+            self.skip_depth -= 1;
+            return Ok(());
+        }
+        self.inner.end_elem(name)
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        if self.skip_depth > 0 {
+            return Ok(());
+        }
+        self.inner.write_text(text)
+    }
+
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        if self.skip_depth > 0 {
+            return Ok(());
+        }
+        self.inner.write_text_with_entities(text, entities)
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        if self.skip_depth > 0 {
+            return Ok(());
+        }
+        self.inner.write_comment(text)
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        if self.skip_depth > 0 {
+            return Ok(());
+        }
+        self.inner.write_doctype(name)
+    }
+
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        if self.skip_depth > 0 {
+            return Ok(());
+        }
+        self.inner.write_doctype_raw(raw)
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        if self.skip_depth > 0 {
+            return Ok(());
+        }
+        self.inner.write_processing_instruction(target, data)
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.inner.set_quirks_mode(mode)
+    }
+}
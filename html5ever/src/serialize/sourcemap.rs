@@ -0,0 +1,204 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, Write};
+
+use super::writers::CountingWriter;
+use super::{AttrRef, EntitySpan, HtmlSerializer, Serialize, SerializeOpts, Serializer};
+use interface::QuirksMode;
+use QualName;
+
+/// Serialize `node` like [`serialize`], and write a source map of the output to
+/// `map_writer`, for finding the node that a part of generated HTML came from.
+///
+/// The map is compact JSON such as
+///
+/// ```text
+/// {"version":1,"mappings":[[0,3,0],[3,4,1],[4,8,0]]}
+/// ```
+///
+/// where each mapping is the start and end of a range of output bytes and the
+/// node that wrote them. Nodes are numbered in the order they are serialized,
+/// starting at 0, which stays the same for as long as the tree does. The ranges
+/// are in order and cover the whole output; the bytes of an element's start and
+/// end tags map to the element, and those in between to its descendants.
+///
+/// [`serialize`]: fn.serialize.html
+pub fn serialize_with_sourcemap<Wr, Map, T>(
+    writer: Wr,
+    map_writer: Map,
+    node: &T,
+    opts: SerializeOpts,
+) -> io::Result<()>
+where
+    Wr: Write,
+    Map: Write,
+    T: Serialize,
+{
+    let mut ser = SourceMapSerializer::new(writer, opts.clone());
+    try!(node.serialize(&mut ser, opts.traversal_scope));
+    try!(ser.finish());
+    ser.write_map(map_writer)
+}
+
+/// An `HtmlSerializer` that records which node wrote each part of its output.
+/// See [`serialize_with_sourcemap`].
+///
+/// [`serialize_with_sourcemap`]: fn.serialize_with_sourcemap.html
+pub struct SourceMapSerializer<Wr: Write> {
+    inner: HtmlSerializer<CountingWriter<Wr>>,
+    /// The number the next node will be given.
+    next_node: usize,
+    /// The numbers of the open elements, innermost last.
+    open: Vec<usize>,
+    /// The output ranges as `(start, end, node)`, with adjacent ranges of the
+    /// same node merged.
+    mappings: Vec<(usize, usize, usize)>,
+}
+
+impl<Wr: Write> SourceMapSerializer<Wr> {
+    /// Write to `writer`, numbering nodes from zero.
+    pub fn new(writer: Wr, opts: SerializeOpts) -> Self {
+        SourceMapSerializer {
+            inner: HtmlSerializer::new(CountingWriter::new(writer), opts),
+            next_node: 0,
+            open: vec![],
+            mappings: vec![],
+        }
+    }
+
+    /// Finish serializing, as `HtmlSerializer::finish` does. End tags written
+    /// for elements that are still open are mapped to the innermost of them.
+    pub fn finish(&mut self) -> io::Result<()> {
+        try!(self.inner.finish());
+        let node = self.last_node();
+        self.map(node);
+        Ok(())
+    }
+
+    /// Write the source map of the output so far.
+    pub fn write_map<Map: Write>(&self, mut map_writer: Map) -> io::Result<()> {
+        try!(map_writer.write_all(b"{\"version\":1,\"mappings\":["));
+        for (i, &(start, end, node)) in self.mappings.iter().enumerate() {
+            let comma = if i > 0 { "," } else { "" };
+            try!(write!(map_writer, "{}[{},{},{}]", comma, start, end, node));
+        }
+        map_writer.write_all(b"]}")
+    }
+
+    /// Unwrap the underlying writer.
+    pub fn into_inner(self) -> Wr {
+        self.inner.into_inner().into_inner()
+    }
+
+    fn new_node(&mut self) -> usize {
+        self.next_node += 1;
+        self.next_node - 1
+    }
+
+    /// The innermost open element, or else the node that wrote last.
+    fn last_node(&self) -> usize {
+        match self.open.last() {
+            Some(&node) => node,
+            None => self.mappings.last().map_or(0, |&(_, _, node)| node),
+        }
+    }
+
+    /// Map the output written since the last call to `node`.
+    fn map(&mut self, node: usize) {
+        let start = self.mappings.last().map_or(0, |&(_, end, _)| end);
+        let end = self.inner.writer.count();
+        if end == start {
+            return;
+        }
+        match self.mappings.last_mut() {
+            Some(&mut (_, ref mut last_end, last_node)) if last_node == node => {
+                *last_end = end;
+                return;
+            },
+            _ => (),
+        }
+        self.mappings.push((start, end, node));
+    }
+}
+
+impl<Wr: Write> Serializer for SourceMapSerializer<Wr> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.start_elem_as_written(name, attrs, false)
+    }
+
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        let node = self.new_node();
+        self.open.push(node);
+        try!(self.inner.start_elem_as_written(name, attrs, self_closing));
+        self.map(node);
+        Ok(())
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        let node = self.open.pop().unwrap_or_else(|| self.last_node());
+        try!(self.inner.end_elem(name));
+        self.map(node);
+        Ok(())
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_text_with_entities(text, &[])
+    }
+
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        let node = self.new_node();
+        try!(self.inner.write_text_with_entities(text, entities));
+        self.map(node);
+        Ok(())
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        let node = self.new_node();
+        try!(self.inner.write_comment(text));
+        self.map(node);
+        Ok(())
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        let node = self.new_node();
+        try!(self.inner.write_doctype(name));
+        self.map(node);
+        Ok(())
+    }
+
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        let node = self.new_node();
+        try!(self.inner.write_doctype_raw(raw));
+        self.map(node);
+        Ok(())
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        let node = self.new_node();
+        try!(self.inner.write_processing_instruction(target, data));
+        self.map(node);
+        Ok(())
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.inner.set_quirks_mode(mode)
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::sync::Arc;
+
+/// A function that the values of `style` attributes are passed through, for
+/// `SerializeOpts::style_sanitizer`. It returns the declarations to keep; if
+/// there are none, the attribute is dropped.
+///
+/// Any CSS parser can be plugged in with `new`. `allow_properties` is a simple
+/// one that needs no dependencies.
+#[derive(Clone)]
+pub struct StyleSanitizer(Arc<dyn Fn(&str) -> String + Send + Sync>);
+
+impl StyleSanitizer {
+    /// Pass values through `sanitize`.
+    pub fn new<F>(sanitize: F) -> Self
+    where
+        F: Fn(&str) -> String + Send + Sync + 'static,
+    {
+        StyleSanitizer(Arc::new(sanitize))
+    }
+
+    /// Keep only the declarations of `properties`, such as `color`, with values
+    /// that can't load anything or run code. A value is dropped if it contains
+    /// quotes, backslash escapes, comments, `<`, `>`, `@`, braces, or a function
+    /// other than `rgb`, `rgba`, `hsl`, `hsla` and `calc`, so that `url(...)`
+    /// and `expression(...)` never get through.
+    pub fn allow_properties<I, S>(properties: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let properties: BTreeSet<String> = properties
+            .into_iter()
+            .map(|p| p.into().to_ascii_lowercase())
+            .collect();
+        StyleSanitizer::new(move |style| {
+            let mut kept = vec![];
+            for declaration in style.split(';') {
+                let colon = match declaration.find(':') {
+                    Some(colon) => colon,
+                    None => continue,
+                };
+                let property = declaration[..colon].trim().to_ascii_lowercase();
+                let value = declaration[colon + 1..].trim();
+                if properties.contains(&property) && is_safe_value(value) {
+                    kept.push(format!("{}: {}", property, value));
+                }
+            }
+            kept.join("; ")
+        })
+    }
+
+    /// The declarations of `style` to keep.
+    pub fn sanitize(&self, style: &str) -> String {
+        (self.0)(style)
+    }
+}
+
+impl fmt::Debug for StyleSanitizer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("StyleSanitizer(..)")
+    }
+}
+
+/// Two sanitizers are equal if they are clones of each other.
+impl PartialEq for StyleSanitizer {
+    fn eq(&self, other: &StyleSanitizer) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+fn is_safe_value(value: &str) -> bool {
+    if value.is_empty() || value.contains("/*") {
+        return false;
+    }
+    let forbidden = |c| match c {
+        '"' | '\'' | '\\' | '<' | '>' | '@' | '{' | '}' => true,
+        _ => false,
+    };
+    if value.contains(forbidden) {
+        return false;
+    }
+    value.match_indices('(').all(|(i, _)| {
+        let name_start = value[..i]
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || c == '-'))
+            .map_or(0, |j| j + 1);
+        match &*value[name_start..i].to_ascii_lowercase() {
+            "rgb" | "rgba" | "hsl" | "hsla" | "calc" => true,
+            _ => false,
+        }
+    })
+}
@@ -0,0 +1,208 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, Write};
+
+use super::recorded::Event;
+use super::{AttrRef, EntitySpan, HtmlSerializer, Serialize, SerializeOpts, Serializer};
+use interface::QuirksMode;
+use QualName;
+
+/// Serialize `node` like [`serialize`], but with the contents of large or deeply
+/// nested elements replaced by a count of the nodes left out, as in
+/// `<ul>…(42 nodes)…</ul>`, for a preview of the document in a log. See
+/// [`SummarizingSerializer`] for which elements are summarized.
+///
+/// [`serialize`]: fn.serialize.html
+/// [`SummarizingSerializer`]: struct.SummarizingSerializer.html
+pub fn serialize_summarized<Wr, T>(
+    writer: Wr,
+    node: &T,
+    opts: SerializeOpts,
+    max_depth: usize,
+    max_nodes: usize,
+) -> io::Result<()>
+where
+    Wr: Write,
+    T: Serialize,
+{
+    let mut ser = SummarizingSerializer::new(
+        HtmlSerializer::new(writer, opts.clone()),
+        max_depth,
+        max_nodes,
+    );
+    try!(node.serialize(&mut ser, opts.traversal_scope));
+    try!(ser.finish()).finish()
+}
+
+/// A `Serializer` that passes everything on to another one, except the contents
+/// of elements that are nested `max_depth` elements deep, so that nothing deeper
+/// is written, or that are made of more than `max_nodes` nodes. Those are
+/// replaced by a text node such as `…(42 nodes)…`, while the element's own tags
+/// are kept.
+///
+/// Every element, text node, comment and processing instruction counts as a
+/// node. Of an element and its descendants that are all too large, only the
+/// innermost ones are summarized, so that the structure around a long list
+/// still shows.
+///
+/// Since the size of an element is only known once it has ended, nothing is
+/// passed on until [`finish`] is called.
+///
+/// [`finish`]: #method.finish
+pub struct SummarizingSerializer<S> {
+    inner: S,
+    max_depth: usize,
+    max_nodes: usize,
+    events: Vec<Event>,
+}
+
+impl<S: Serializer> SummarizingSerializer<S> {
+    /// Pass everything on to `inner`, summarizing elements nested `max_depth`
+    /// deep or made of more than `max_nodes` nodes.
+    pub fn new(inner: S, max_depth: usize, max_nodes: usize) -> Self {
+        SummarizingSerializer {
+            inner: inner,
+            max_depth: max_depth,
+            max_nodes: max_nodes,
+            events: vec![],
+        }
+    }
+
+    /// Pass everything received so far on to the inner serializer, summarized,
+    /// and return it.
+    pub fn finish(mut self) -> io::Result<S> {
+        let events = ::std::mem::replace(&mut self.events, vec![]);
+        // For each start tag, the index of its end tag and the number of
+        // nodes in between.
+        let mut ends = vec![events.len(); events.len()];
+        let mut sizes = vec![0; events.len()];
+        let mut open = vec![];
+        for (i, event) in events.iter().enumerate() {
+            match *event {
+                Event::End(_) => {
+                    if let Some(start) = open.pop() {
+                        ends[start] = i;
+                    }
+                    continue;
+                },
+                Event::Doctype(_) | Event::DoctypeRaw(_) => continue,
+                _ => (),
+            }
+            for &start in open.iter() {
+                sizes[start] += 1;
+            }
+            if let Event::Start(..) = *event {
+                open.push(i);
+            }
+        }
+
+        let max_nodes = self.max_nodes;
+        let too_large = |i: usize| sizes[i] > max_nodes;
+        let mut depth = 0;
+        let mut i = 0;
+        while i < events.len() {
+            match events[i] {
+                Event::Start(..) => {
+                    depth += 1;
+                    try!(events[i].replay(&mut self.inner));
+                    let mut child_too_large = false;
+                    let mut j = i + 1;
+                    while j < ends[i] {
+                        if let Event::Start(..) = events[j] {
+                            child_too_large |= too_large(j);
+                            j = ends[j];
+                        }
+                        j += 1;
+                    }
+                    let summarize = depth >= self.max_depth || (too_large(i) && !child_too_large);
+                    if summarize && sizes[i] > 0 {
+                        let nodes = if sizes[i] == 1 { "node" } else { "nodes" };
+                        try!(self
+                            .inner
+                            .write_text(&format!("…({} {})…", sizes[i], nodes)));
+                        // Resume at the end tag.
+                        i = ends[i];
+                        continue;
+                    }
+                },
+                Event::End(_) => {
+                    depth -= 1;
+                    try!(events[i].replay(&mut self.inner));
+                },
+                ref event => try!(event.replay(&mut self.inner)),
+            }
+            i += 1;
+        }
+        Ok(self.inner)
+    }
+}
+
+impl<S: Serializer> Serializer for SummarizingSerializer<S> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.start_elem_as_written(name, attrs, false)
+    }
+
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.events.push(Event::start(name, attrs, self_closing));
+        Ok(())
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        self.events.push(Event::End(name));
+        Ok(())
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.write_text_with_entities(text, &[])
+    }
+
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        self.events.push(Event::text(text, entities));
+        Ok(())
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.events.push(Event::Comment(text.to_owned()));
+        Ok(())
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.events.push(Event::Doctype(name.to_owned()));
+        Ok(())
+    }
+
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        self.events.push(Event::DoctypeRaw(raw.to_owned()));
+        Ok(())
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.events.push(Event::ProcessingInstruction(
+            target.to_owned(),
+            data.to_owned(),
+        ));
+        Ok(())
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.inner.set_quirks_mode(mode)
+    }
+}
@@ -0,0 +1,143 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io;
+
+use super::{AttrRef, Serialize, Serializer, TraversalScope};
+use QualName;
+
+/// The text inside `node`, like the DOM's `textContent`: all of its descendant
+/// text concatenated, without markup or comments.
+pub fn serialize_text_content<T: Serialize>(node: &T) -> String {
+    let mut ser = TextOnlySerializer::new(false);
+    node.serialize(&mut ser, TraversalScope::ChildrenOnly(None))
+        .expect("TextOnlySerializer never fails");
+    ser.into_string()
+}
+
+/// A `Serializer` that collects the text of the nodes it is given and ignores
+/// everything else.
+pub struct TextOnlySerializer {
+    text: String,
+    block_separators: bool,
+    pending_separator: bool,
+}
+
+impl TextOnlySerializer {
+    /// With `block_separators`, a space is inserted where a block-level element
+    /// such as `<p>` or `<li>` starts or ends, unless the text already has
+    /// whitespace there, so that `<p>a</p><p>b</p>` gives `a b` instead of `ab`.
+    pub fn new(block_separators: bool) -> Self {
+        TextOnlySerializer {
+            text: String::new(),
+            block_separators: block_separators,
+            pending_separator: false,
+        }
+    }
+
+    /// The text collected so far.
+    pub fn into_string(self) -> String {
+        self.text
+    }
+
+    fn block_boundary(&mut self, name: &QualName) {
+        if self.block_separators && is_block_element(name) {
+            self.pending_separator = true;
+        }
+    }
+}
+
+pub fn is_block_element(name: &QualName) -> bool {
+    name.ns == ns!(html)
+        && match name.local {
+            local_name!("address")
+            | local_name!("article")
+            | local_name!("aside")
+            | local_name!("blockquote")
+            | local_name!("br")
+            | local_name!("dd")
+            | local_name!("details")
+            | local_name!("div")
+            | local_name!("dl")
+            | local_name!("dt")
+            | local_name!("fieldset")
+            | local_name!("figcaption")
+            | local_name!("figure")
+            | local_name!("footer")
+            | local_name!("form")
+            | local_name!("h1")
+            | local_name!("h2")
+            | local_name!("h3")
+            | local_name!("h4")
+            | local_name!("h5")
+            | local_name!("h6")
+            | local_name!("header")
+            | local_name!("hr")
+            | local_name!("li")
+            | local_name!("main")
+            | local_name!("nav")
+            | local_name!("ol")
+            | local_name!("p")
+            | local_name!("pre")
+            | local_name!("section")
+            | local_name!("summary")
+            | local_name!("table")
+            | local_name!("td")
+            | local_name!("th")
+            | local_name!("tr")
+            | local_name!("ul") => true,
+            _ => false,
+        }
+}
+
+impl Serializer for TextOnlySerializer {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, _attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.block_boundary(&name);
+        Ok(())
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        self.block_boundary(&name);
+        Ok(())
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        if text.is_empty() {
+            return Ok(());
+        }
+        if self.pending_separator {
+            let after_space = self
+                .text
+                .chars()
+                .next_back()
+                .map_or(true, char::is_whitespace);
+            if !after_space && !text.starts_with(char::is_whitespace) {
+                self.text.push(' ');
+            }
+            self.pending_separator = false;
+        }
+        self.text.push_str(text);
+        Ok(())
+    }
+
+    fn write_comment(&mut self, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_doctype(&mut self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_processing_instruction(&mut self, _target: &str, _data: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
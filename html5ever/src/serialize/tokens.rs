@@ -0,0 +1,192 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, Write};
+
+use super::{escape_comment, html_text_mode, HtmlSerializer, SerializeOpts, TextMode};
+use tokenizer::{Doctype, EndTag, StartTag, Tag, Token};
+use LocalName;
+
+/// Write `tokens`, as produced by the tokenizer, back out as HTML. See
+/// [`TokenSerializer`].
+///
+/// [`TokenSerializer`]: struct.TokenSerializer.html
+pub fn serialize_tokens<Wr: Write>(
+    writer: Wr,
+    tokens: &[Token],
+    opts: SerializeOpts,
+) -> io::Result<()> {
+    let mut ser = TokenSerializer::new(writer, opts);
+    for token in tokens {
+        try!(ser.write_token(token));
+    }
+    Ok(())
+}
+
+/// Writes a stream of tokenizer `Token`s as HTML, one token at a time, without
+/// building a tree first. Unlike serializing a tree, this keeps what a tree
+/// builder would drop or move around: stray and misnested end tags, the
+/// `/` of self-closing start tags, attributes of end tags, and the public and
+/// system identifiers of doctypes.
+///
+/// Text and attribute values are escaped as `HtmlSerializer` would with the
+/// same options. Other options, which are about trees, are ignored.
+/// `NullCharacterToken`s are written as U+0000, so that tokenizing the output
+/// gives them back, and `EOFToken`s and `ParseError`s are skipped.
+///
+/// A plain `Tokenizer` reads the text of a `<script>` or `<style>` like any
+/// other, decoding character references, so by default that text is escaped
+/// too. Only if the tokens come from a tokenizer whose sink switches it to the
+/// raw text states, as the tree builder does, is the text of those elements
+/// written as it is; see [`with_raw_text`].
+///
+/// The tokenizer allows characters in names that can't be written back, such
+/// as `"` and `=`. A tag with such a name is written as text, escaped, and an
+/// attribute with one is dropped.
+///
+/// [`with_raw_text`]: #method.with_raw_text
+pub struct TokenSerializer<Wr: Write> {
+    inner: HtmlSerializer<Wr>,
+    /// Whether the tokenizer read the text of raw text elements as raw text.
+    raw_text_states: bool,
+    /// The raw text element whose text is being written, if any.
+    raw_text: Option<LocalName>,
+}
+
+impl<Wr: Write> TokenSerializer<Wr> {
+    /// Write to `writer`, escaping as `HtmlSerializer` would with `opts`.
+    pub fn new(writer: Wr, opts: SerializeOpts) -> Self {
+        TokenSerializer {
+            inner: HtmlSerializer::new(writer, opts),
+            raw_text_states: false,
+            raw_text: None,
+        }
+    }
+
+    /// Like `new`, for tokens from a tokenizer that was switched to the raw
+    /// text states for `<script>`, `<style>` and the like, whose text is then
+    /// written as it is. Text that a plain `Tokenizer` read in the data state
+    /// must not be passed to this, since its references have been decoded.
+    pub fn with_raw_text(writer: Wr, opts: SerializeOpts) -> Self {
+        TokenSerializer {
+            raw_text_states: true,
+            ..TokenSerializer::new(writer, opts)
+        }
+    }
+
+    /// Unwrap the underlying writer.
+    pub fn into_inner(self) -> Wr {
+        self.inner.into_inner()
+    }
+
+    /// Write one token.
+    pub fn write_token(&mut self, token: &Token) -> io::Result<()> {
+        match *token {
+            Token::DoctypeToken(ref doctype) => self.write_doctype(doctype),
+            Token::TagToken(ref tag) => self.write_tag(tag),
+            Token::CommentToken(ref text) => {
+                try!(self.inner.writer.write_all(b"<!--"));
+                try!(self.inner.writer.write_all(escape_comment(text).as_bytes()));
+                self.inner.writer.write_all(b"-->")
+            },
+            Token::CharacterTokens(ref text) => {
+                if self.raw_text.is_some() {
+                    self.inner.writer.write_all(text.as_bytes())
+                } else {
+                    self.inner.write_escaped(text, false)
+                }
+            },
+            Token::NullCharacterToken => self.inner.writer.write_all(b"\0"),
+            Token::EOFToken | Token::ParseError(_) => Ok(()),
+        }
+    }
+
+    fn write_tag(&mut self, tag: &Tag) -> io::Result<()> {
+        let escape = !is_writable_name(&tag.name);
+        match tag.kind {
+            StartTag => {
+                if self.raw_text_states && html_text_mode(&tag.name) == TextMode::RawText {
+                    self.raw_text = Some(tag.name.clone());
+                }
+                try!(self.inner.write_markup("<", escape));
+            },
+            EndTag => {
+                // `<plaintext>` has no end tag; everything after it is text.
+                if self.raw_text.as_ref() == Some(&tag.name) && tag.name != local_name!("plaintext")
+                {
+                    self.raw_text = None;
+                }
+                try!(self.inner.write_markup("</", escape));
+            },
+        }
+        try!(self.inner.write_name(&tag.name, escape));
+        for attr in tag.attrs.iter() {
+            if !is_writable_name(&attr.name.local) {
+                warn!(
+                    "dropping attribute with unwritable name {:?}",
+                    attr.name.local
+                );
+                continue;
+            }
+            try!(self.inner.write_markup(" ", escape));
+            try!(self.inner.write_name(&attr.name.local, escape));
+            try!(self.inner.write_markup("=\"", escape));
+            try!(self.inner.write_escaped(&attr.value, !escape));
+            try!(self.inner.write_markup("\"", escape));
+        }
+        if tag.self_closing {
+            try!(self.inner.write_markup("/", escape));
+        }
+        self.inner.write_markup(">", escape)
+    }
+
+    fn write_doctype(&mut self, doctype: &Doctype) -> io::Result<()> {
+        try!(self.inner.writer.write_all(b"<!DOCTYPE"));
+        if let Some(ref name) = doctype.name {
+            try!(self.inner.writer.write_all(b" "));
+            try!(self.inner.writer.write_all(name.as_bytes()));
+        }
+        match (&doctype.public_id, &doctype.system_id) {
+            (&Some(ref public_id), system_id) => {
+                try!(self.inner.writer.write_all(b" PUBLIC "));
+                try!(self.write_quoted(public_id));
+                if let Some(ref system_id) = *system_id {
+                    try!(self.inner.writer.write_all(b" "));
+                    try!(self.write_quoted(system_id));
+                }
+            },
+            (&None, &Some(ref system_id)) => {
+                try!(self.inner.writer.write_all(b" SYSTEM "));
+                try!(self.write_quoted(system_id));
+            },
+            (&None, &None) => (),
+        }
+        self.inner.writer.write_all(b">")
+    }
+
+    /// Quote a doctype identifier, which can't contain character references,
+    /// with whichever quote it doesn't contain.
+    fn write_quoted(&mut self, id: &str) -> io::Result<()> {
+        let quote: &[u8] = if id.contains('"') { b"'" } else { b"\"" };
+        try!(self.inner.writer.write_all(quote));
+        try!(self.inner.writer.write_all(id.as_bytes()));
+        self.inner.writer.write_all(quote)
+    }
+}
+
+/// Whether `name` can be written as a tag or attribute name and read back the
+/// same: it is not empty, and has no whitespace, quotes, `/`, `<`, `=`, `>` or
+/// U+0000.
+fn is_writable_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.contains(|c| match c {
+            '\t' | '\n' | '\x0C' | '\r' | ' ' | '"' | '\'' | '/' | '<' | '=' | '>' | '\0' => true,
+            _ => false,
+        })
+}
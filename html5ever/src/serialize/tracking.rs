@@ -0,0 +1,165 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeSet;
+use std::io::{self, Write};
+
+use super::url::remove_query_params;
+use super::{
+    is_url_attribute, AttrRef, EntitySpan, HtmlSerializer, Serialize, SerializeOpts, Serializer,
+};
+use interface::QuirksMode;
+use QualName;
+
+/// Serialize `node` like [`serialize`], without the tracking that
+/// [`StripTrackingSerializer`] removes, using its default list of query
+/// parameters.
+///
+/// [`serialize`]: fn.serialize.html
+/// [`StripTrackingSerializer`]: struct.StripTrackingSerializer.html
+pub fn serialize_without_tracking<Wr, T>(
+    writer: Wr,
+    node: &T,
+    opts: SerializeOpts,
+) -> io::Result<()>
+where
+    Wr: Write,
+    T: Serialize,
+{
+    let mut ser = StripTrackingSerializer::new(HtmlSerializer::new(writer, opts.clone()));
+    try!(node.serialize(&mut ser, opts.traversal_scope));
+    ser.into_inner().finish()
+}
+
+/// A `Serializer` that passes everything on to another one, without the
+/// attributes that track readers: `ping` on `<a>` and `<area>`, which reports
+/// each click, `referrerpolicy`, which can make a page send more than it would
+/// by default, and the tracking parameters in the query of URL attributes such
+/// as `href` and `src`.
+pub struct StripTrackingSerializer<S> {
+    inner: S,
+    params: BTreeSet<String>,
+}
+
+impl<S: Serializer> StripTrackingSerializer<S> {
+    /// Wrap `inner`, removing the parameters in `default_params`.
+    pub fn new(inner: S) -> Self {
+        StripTrackingSerializer::with_params(inner, StripTrackingSerializer::<S>::default_params())
+    }
+
+    /// Wrap `inner`, removing the query parameters named in `params`.
+    pub fn with_params(inner: S, params: BTreeSet<String>) -> Self {
+        StripTrackingSerializer {
+            inner: inner,
+            params: params,
+        }
+    }
+
+    /// The common campaign and click tracking parameters: the five `utm_` ones,
+    /// `gclid`, `fbclid`, `msclkid`, `mc_cid` and `mc_eid`.
+    pub fn default_params() -> BTreeSet<String> {
+        [
+            "utm_source",
+            "utm_medium",
+            "utm_campaign",
+            "utm_term",
+            "utm_content",
+            "gclid",
+            "fbclid",
+            "msclkid",
+            "mc_cid",
+            "mc_eid",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    }
+
+    /// Unwrap the inner serializer.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+fn is_tracking_attribute(elem: &QualName, attr: &QualName) -> bool {
+    if attr.ns != ns!() {
+        return false;
+    }
+    match attr.local {
+        local_name!("ping") => {
+            elem.ns == ns!(html)
+                && (elem.local == local_name!("a") || elem.local == local_name!("area"))
+        },
+        ref local => &**local == "referrerpolicy",
+    }
+}
+
+impl<S: Serializer> Serializer for StripTrackingSerializer<S> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.start_elem_as_written(name, attrs, false)
+    }
+
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        let attrs: Vec<_> = attrs
+            .filter(|&(attr, _)| !is_tracking_attribute(&name, attr))
+            .map(|(attr, value)| {
+                if is_url_attribute(attr) {
+                    (attr, remove_query_params(value, &self.params))
+                } else {
+                    (attr, value.into())
+                }
+            })
+            .collect();
+        let attrs = attrs.iter().map(|&(attr, ref value)| (attr, &**value));
+        self.inner.start_elem_as_written(name, attrs, self_closing)
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        self.inner.end_elem(name)
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.inner.write_text(text)
+    }
+
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        self.inner.write_text_with_entities(text, entities)
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.inner.write_comment(text)
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.inner.write_doctype(name)
+    }
+
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        self.inner.write_doctype_raw(raw)
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.inner.write_processing_instruction(target, data)
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.inner.set_quirks_mode(mode)
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Reference resolution as described in [RFC 3986, section 5][rfc], removal of
+//! query parameters, and filtering of `srcset` image candidates. URLs are
+//! treated as strings; nothing is percent-encoded or normalized.
+//!
+//! [rfc]: https://tools.ietf.org/html/rfc3986#section-5
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+
+struct Parts<'a> {
+    scheme: Option<&'a str>,
+    authority: Option<&'a str>,
+    path: &'a str,
+    query: Option<&'a str>,
+    fragment: Option<&'a str>,
+}
+
+fn split<'a>(uri: &'a str) -> Parts<'a> {
+    let (uri, fragment) = match uri.find('#') {
+        Some(i) => (&uri[..i], Some(&uri[i + 1..])),
+        None => (uri, None),
+    };
+    let (uri, query) = match uri.find('?') {
+        Some(i) => (&uri[..i], Some(&uri[i + 1..])),
+        None => (uri, None),
+    };
+    let (scheme, uri) = match uri.find(|c| c == ':' || c == '/') {
+        Some(i) if i > 0 && uri[i..].starts_with(':') => (Some(&uri[..i]), &uri[i + 1..]),
+        _ => (None, uri),
+    };
+    let (authority, path) = if uri.starts_with("//") {
+        let uri = &uri[2..];
+        match uri.find('/') {
+            Some(i) => (Some(&uri[..i]), &uri[i..]),
+            None => (Some(uri), ""),
+        }
+    } else {
+        (None, uri)
+    };
+    Parts {
+        scheme: scheme,
+        authority: authority,
+        path: path,
+        query: query,
+        fragment: fragment,
+    }
+}
+
+fn remove_dot_segments(mut input: &str) -> String {
+    // Each output segment keeps its leading '/', if it has one.
+    let mut output: Vec<&str> = vec![];
+    while !input.is_empty() {
+        if input.starts_with("../") {
+            input = &input[3..];
+        } else if input.starts_with("./") || input.starts_with("/./") {
+            input = &input[2..];
+        } else if input == "/." {
+            input = "/";
+        } else if input.starts_with("/../") {
+            input = &input[3..];
+            output.pop();
+        } else if input == "/.." {
+            input = "/";
+            output.pop();
+        } else if input == "." || input == ".." {
+            input = "";
+        } else {
+            let end = input.as_bytes()[1..]
+                .iter()
+                .position(|&b| b == b'/')
+                .map_or(input.len(), |i| i + 1);
+            output.push(&input[..end]);
+            input = &input[end..];
+        }
+    }
+    output.concat()
+}
+
+/// Resolve `reference` against the absolute URL `base`. Returns `None` if `base`
+/// has no scheme.
+pub fn resolve(base: &str, reference: &str) -> Option<String> {
+    let base = split(base);
+    let base_scheme = match base.scheme {
+        Some(scheme) => scheme,
+        None => return None,
+    };
+    let r = split(reference.trim_matches(|c: char| c <= ' '));
+
+    let (scheme, authority, path, query);
+    if let Some(s) = r.scheme {
+        scheme = s;
+        authority = r.authority;
+        path = remove_dot_segments(r.path);
+        query = r.query;
+    } else {
+        scheme = base_scheme;
+        if r.authority.is_some() {
+            authority = r.authority;
+            path = remove_dot_segments(r.path);
+            query = r.query;
+        } else {
+            authority = base.authority;
+            if r.path.is_empty() {
+                path = base.path.to_owned();
+                query = r.query.or(base.query);
+            } else {
+                let merged = if r.path.starts_with('/') {
+                    r.path.to_owned()
+                } else if base.authority.is_some() && base.path.is_empty() {
+                    format!("/{}", r.path)
+                } else {
+                    let dir = base.path.rfind('/').map_or(0, |i| i + 1);
+                    format!("{}{}", &base.path[..dir], r.path)
+                };
+                path = remove_dot_segments(&merged);
+                query = r.query;
+            }
+        }
+    }
+
+    let mut result = format!("{}:", scheme);
+    if let Some(authority) = authority {
+        result.push_str("//");
+        result.push_str(authority);
+    }
+    result.push_str(&path);
+    if let Some(query) = query {
+        result.push('?');
+        result.push_str(query);
+    }
+    if let Some(fragment) = r.fragment {
+        result.push('#');
+        result.push_str(fragment);
+    }
+    Some(result)
+}
+
+/// `url` without the query parameters named in `params`. The `?` is left out
+/// too if no parameters are left; everything else is kept as it was.
+pub fn remove_query_params<'a>(url: &'a str, params: &BTreeSet<String>) -> Cow<'a, str> {
+    let (rest, fragment) = match url.find('#') {
+        Some(i) => (&url[..i], &url[i..]),
+        None => (url, ""),
+    };
+    let (path, query) = match rest.find('?') {
+        Some(i) => (&rest[..i], &rest[i + 1..]),
+        None => return Cow::Borrowed(url),
+    };
+    let is_removed = |pair: &str| {
+        let name = pair.split('=').next().unwrap_or("");
+        params.contains(name)
+    };
+    if !query.split('&').any(|pair| is_removed(pair)) {
+        return Cow::Borrowed(url);
+    }
+    let kept: Vec<&str> = query.split('&').filter(|pair| !is_removed(pair)).collect();
+    let mut result = path.to_owned();
+    if !kept.is_empty() {
+        result.push('?');
+        result.push_str(&kept.join("&"));
+    }
+    result.push_str(fragment);
+    Cow::Owned(result)
+}
+
+/// The image candidates of a `srcset` attribute value, as `(url, descriptors)`
+/// pairs, split as the [HTML standard][spec] does: the URL runs to the next
+/// whitespace, less any trailing commas, and the descriptors to the next comma
+/// that isn't in parentheses.
+///
+/// [spec]: https://html.spec.whatwg.org/multipage/#parse-a-srcset-attribute
+fn srcset_candidates(srcset: &str) -> Vec<(&str, &str)> {
+    let mut candidates = vec![];
+    let mut rest = srcset;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_ascii_whitespace() || c == ',');
+        if rest.is_empty() {
+            return candidates;
+        }
+        let end = rest
+            .find(|c: char| c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let url = &rest[..end];
+        rest = &rest[end..];
+        if url.ends_with(',') {
+            candidates.push((url.trim_end_matches(','), ""));
+            continue;
+        }
+        let mut depth = 0;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| {
+                match c {
+                    '(' => depth += 1,
+                    ')' if depth > 0 => depth -= 1,
+                    ',' if depth == 0 => return true,
+                    _ => (),
+                }
+                false
+            })
+            .map_or(rest.len(), |(i, _)| i);
+        candidates.push((url, rest[..end].trim()));
+        rest = &rest[end..];
+    }
+}
+
+/// `srcset` without the image candidates whose URL `keep` returns false for.
+/// If any are removed, the rest are written as `url descriptors`, separated by
+/// `, `.
+pub fn filter_srcset<'a, F>(srcset: &'a str, keep: F) -> Cow<'a, str>
+where
+    F: Fn(&str) -> bool,
+{
+    let candidates = srcset_candidates(srcset);
+    if candidates.iter().all(|&(url, _)| keep(url)) {
+        return Cow::Borrowed(srcset);
+    }
+    let kept: Vec<String> = candidates
+        .into_iter()
+        .filter(|&(url, _)| keep(url))
+        .map(|(url, descriptors)| match descriptors {
+            "" => url.to_owned(),
+            _ => format!("{} {}", url, descriptors),
+        })
+        .collect();
+    Cow::Owned(kept.join(", "))
+}
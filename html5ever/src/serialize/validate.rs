@@ -0,0 +1,277 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io;
+
+use super::{
+    has_unknown_namespace, is_custom_element_allowed, is_script_allowed, is_void_element, AttrRef,
+    EntitySpan, HtmlSerializer, Serialize, SerializeOpts, Serializer,
+};
+use interface::QuirksMode;
+use QualName;
+
+/// A problem with the sequence of calls made to a `ValidatingSerializer`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Diagnostic {
+    /// An element was given the same attribute more than once.
+    DuplicateAttribute {
+        element: QualName,
+        attribute: QualName,
+    },
+    /// A void element, such as `<br>`, was given children.
+    VoidElementWithChildren(QualName),
+    /// An end tag didn't match the innermost open element.
+    MismatchedEndTag { open: QualName, end: QualName },
+    /// An end tag came when no element was open.
+    UnmatchedEndTag(QualName),
+    /// An element was still open at the end.
+    UnclosedElement(QualName),
+    /// An element or attribute is in a namespace that HTML has no syntax for,
+    /// and `lossless_namespaces` isn't set.
+    UnknownNamespace(QualName),
+    /// An element isn't allowed by `allowed_tags`, `allowed_is_values` or
+    /// `allowed_script_srcs`, so its tags would be escaped.
+    DisallowedElement(QualName),
+}
+
+struct OpenElement {
+    name: QualName,
+    /// Whether a child of this void element has already been reported.
+    reported_child: bool,
+}
+
+/// A `Serializer` that passes everything on to another one unchanged, and
+/// records a `Diagnostic` for anything that would make the output malformed.
+pub struct ValidatingSerializer<S> {
+    inner: S,
+    stack: Vec<OpenElement>,
+    diagnostics: Vec<Diagnostic>,
+    /// The options to check elements against, if any.
+    opts: Option<SerializeOpts>,
+}
+
+impl<S: Serializer> ValidatingSerializer<S> {
+    /// Wrap `inner`, without checking elements against any options.
+    pub fn new(inner: S) -> Self {
+        ValidatingSerializer {
+            inner: inner,
+            stack: vec![],
+            diagnostics: vec![],
+            opts: None,
+        }
+    }
+
+    /// Like `new`, but also report what `HtmlSerializer` would write differently
+    /// from the tree with `opts`: elements it would escape, and names it can't
+    /// write in their namespace.
+    pub fn with_opts(inner: S, opts: SerializeOpts) -> Self {
+        ValidatingSerializer {
+            opts: Some(opts),
+            ..ValidatingSerializer::new(inner)
+        }
+    }
+
+    /// The problems found so far.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Unwrap the inner serializer, along with all the problems found. Any
+    /// elements that are still open are reported as unclosed.
+    pub fn into_parts(mut self) -> (S, Vec<Diagnostic>) {
+        for open in self.stack.drain(..) {
+            self.diagnostics
+                .push(Diagnostic::UnclosedElement(open.name));
+        }
+        (self.inner, self.diagnostics)
+    }
+
+    fn check_element(&mut self, name: &QualName, attrs: &[AttrRef]) {
+        let opts = match self.opts {
+            Some(ref opts) => opts,
+            None => return,
+        };
+        if !opts.lossless_namespaces {
+            if has_unknown_namespace(name, false) {
+                self.diagnostics
+                    .push(Diagnostic::UnknownNamespace(name.clone()));
+            }
+            for &(attr, _) in attrs {
+                if has_unknown_namespace(attr, true) {
+                    self.diagnostics
+                        .push(Diagnostic::UnknownNamespace(attr.clone()));
+                }
+            }
+        }
+        let tag = if name.ns == ns!(html) {
+            opts.tag_rewrite.get(&name.local).unwrap_or(&name.local)
+        } else {
+            &name.local
+        };
+        let allowed = opts
+            .allowed_tags
+            .as_ref()
+            .map_or(true, |tags| tags.contains(tag));
+        if !allowed
+            || !is_custom_element_allowed(opts, attrs)
+            || !is_script_allowed(opts, name, attrs)
+        {
+            self.diagnostics
+                .push(Diagnostic::DisallowedElement(name.clone()));
+        }
+    }
+
+    fn check_child(&mut self) {
+        if let Some(parent) = self.stack.last_mut() {
+            if !parent.reported_child && is_void_element(&parent.name) {
+                parent.reported_child = true;
+                self.diagnostics
+                    .push(Diagnostic::VoidElementWithChildren(parent.name.clone()));
+            }
+        }
+    }
+}
+
+impl<S: Serializer> Serializer for ValidatingSerializer<S> {
+    fn start_elem<'a, AttrIter>(&mut self, name: QualName, attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.start_elem_as_written(name, attrs, false)
+    }
+
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        self.check_child();
+        let attrs: Vec<AttrRef> = attrs.collect();
+        for (i, &(attr, _)) in attrs.iter().enumerate() {
+            let first = attrs.iter().position(|&(other, _)| other == attr);
+            if first != Some(i) {
+                continue;
+            }
+            if attrs[i + 1..].iter().any(|&(other, _)| other == attr) {
+                self.diagnostics.push(Diagnostic::DuplicateAttribute {
+                    element: name.clone(),
+                    attribute: attr.clone(),
+                });
+            }
+        }
+        self.check_element(&name, &attrs);
+        self.stack.push(OpenElement {
+            name: name.clone(),
+            reported_child: false,
+        });
+        self.inner
+            .start_elem_as_written(name, attrs.into_iter(), self_closing)
+    }
+
+    fn end_elem(&mut self, name: QualName) -> io::Result<()> {
+        match self.stack.pop() {
+            Some(ref open) if open.name == name => (),
+            Some(open) => self.diagnostics.push(Diagnostic::MismatchedEndTag {
+                open: open.name,
+                end: name.clone(),
+            }),
+            None => self
+                .diagnostics
+                .push(Diagnostic::UnmatchedEndTag(name.clone())),
+        }
+        self.inner.end_elem(name)
+    }
+
+    fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.check_child();
+        self.inner.write_text(text)
+    }
+
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        self.check_child();
+        self.inner.write_text_with_entities(text, entities)
+    }
+
+    fn write_comment(&mut self, text: &str) -> io::Result<()> {
+        self.check_child();
+        self.inner.write_comment(text)
+    }
+
+    fn write_doctype(&mut self, name: &str) -> io::Result<()> {
+        self.check_child();
+        self.inner.write_doctype(name)
+    }
+
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        self.check_child();
+        self.inner.write_doctype_raw(raw)
+    }
+
+    fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()> {
+        self.check_child();
+        self.inner.write_processing_instruction(target, data)
+    }
+
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        self.inner.set_quirks_mode(mode)
+    }
+}
+
+/// A `Serializer` that writes nothing.
+struct Discard;
+
+impl Serializer for Discard {
+    fn start_elem<'a, AttrIter>(&mut self, _name: QualName, _attrs: AttrIter) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        Ok(())
+    }
+
+    fn end_elem(&mut self, _name: QualName) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_text(&mut self, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_comment(&mut self, _text: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_doctype(&mut self, _name: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_processing_instruction(&mut self, _target: &str, _data: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl HtmlSerializer<io::Sink> {
+    /// Check `node` without serializing it, with a `ValidatingSerializer` using
+    /// `opts`, and return everything it finds.
+    pub fn validate<T: Serialize>(node: &T, opts: SerializeOpts) -> Result<(), Vec<Diagnostic>> {
+        let traversal_scope = opts.traversal_scope.clone();
+        let mut validator = ValidatingSerializer::with_opts(Discard, opts);
+        node.serialize(&mut validator, traversal_scope)
+            .expect("serializing to Discard never fails");
+        let (_, diagnostics) = validator.into_parts();
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(diagnostics)
+        }
+    }
+}
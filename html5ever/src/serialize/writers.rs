@@ -0,0 +1,401 @@
+// Copyright 2014-2017 The html5ever Project Developers. See the
+// COPYRIGHT file at the top-level directory of this distribution.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! `Write` adapters for use with the serializer.
+
+use std::io::{self, Write};
+use std::str;
+
+/// A writer that hands its output to a callback in chunks of a fixed size.
+///
+/// Every chunk is exactly `chunk_size` bytes long, except the last one, which
+/// is passed on by `flush`. Chunk boundaries fall wherever the size is reached,
+/// so a chunk may end in the middle of a UTF-8 sequence.
+pub struct ChunkedWriter<F: FnMut(&[u8])> {
+    buf: Vec<u8>,
+    chunk_size: usize,
+    on_chunk: F,
+}
+
+impl<F: FnMut(&[u8])> ChunkedWriter<F> {
+    /// Panics if `chunk_size` is zero.
+    pub fn new(chunk_size: usize, on_chunk: F) -> Self {
+        assert!(chunk_size > 0, "chunk_size must not be zero");
+        ChunkedWriter {
+            buf: Vec::with_capacity(chunk_size),
+            chunk_size: chunk_size,
+            on_chunk: on_chunk,
+        }
+    }
+}
+
+impl<F: FnMut(&[u8])> Write for ChunkedWriter<F> {
+    fn write(&mut self, mut data: &[u8]) -> io::Result<usize> {
+        let len = data.len();
+        while !data.is_empty() {
+            let n = (self.chunk_size - self.buf.len()).min(data.len());
+            self.buf.extend_from_slice(&data[..n]);
+            data = &data[n..];
+            if self.buf.len() == self.chunk_size {
+                (self.on_chunk)(&self.buf);
+                self.buf.clear();
+            }
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            (self.on_chunk)(&self.buf);
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+/// A writer that HTML-escapes everything written to it before passing it on.
+///
+/// In text mode `&`, `<`, `>` and no-break spaces are escaped; in attribute
+/// mode, `&`, `"` and no-break spaces. The input must be UTF-8, but a character
+/// may be split across calls to `write`: its first bytes are held back until the
+/// rest arrives.
+pub struct EscapingWriter<W: Write> {
+    inner: W,
+    attr_mode: bool,
+    /// The start of a character split across writes.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> EscapingWriter<W> {
+    /// Escape as in attribute values if `attr_mode`, or else as in text.
+    pub fn new(inner: W, attr_mode: bool) -> Self {
+        EscapingWriter {
+            inner: inner,
+            attr_mode: attr_mode,
+            pending: vec![],
+        }
+    }
+
+    /// A reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Flush, and unwrap the inner writer. Fails if the input ended in the
+    /// middle of a character.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.pending.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "input ended in the middle of a UTF-8 sequence",
+            ));
+        }
+        try!(self.inner.flush());
+        Ok(self.inner)
+    }
+
+    fn write_escaped(&mut self, text: &str) -> io::Result<()> {
+        let mut escaped = String::with_capacity(text.len());
+        for c in text.chars() {
+            match c {
+                '&' => escaped.push_str("&amp;"),
+                '\u{00A0}' => escaped.push_str("&nbsp;"),
+                '"' if self.attr_mode => escaped.push_str("&quot;"),
+                '<' if !self.attr_mode => escaped.push_str("&lt;"),
+                '>' if !self.attr_mode => escaped.push_str("&gt;"),
+                c => escaped.push(c),
+            }
+        }
+        self.inner.write_all(escaped.as_bytes())
+    }
+}
+
+impl<W: Write> Write for EscapingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut bytes = ::std::mem::replace(&mut self.pending, vec![]);
+        bytes.extend_from_slice(buf);
+        let valid = match str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(e) => match e.error_len() {
+                None => e.valid_up_to(),
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "EscapingWriter input is not UTF-8",
+                    ))
+                },
+            },
+        };
+        try!(self.write_escaped(str::from_utf8(&bytes[..valid]).unwrap()));
+        self.pending = bytes[valid..].to_vec();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer that transcodes the UTF-8 written to it to UTF-16, for consumers
+/// that expect it, starting with a byte order mark.
+///
+/// As with `EscapingWriter`, a character may be split across calls to `write`.
+pub struct Utf16Writer<W: Write> {
+    inner: W,
+    big_endian: bool,
+    bom_written: bool,
+    /// The start of a character split across writes.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Utf16Writer<W> {
+    /// Write UTF-16BE if `big_endian`, or else UTF-16LE.
+    pub fn new(inner: W, big_endian: bool) -> Self {
+        Utf16Writer {
+            inner: inner,
+            big_endian: big_endian,
+            bom_written: false,
+            pending: vec![],
+        }
+    }
+
+    /// A reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Write the byte order mark if nothing has been written, flush, and unwrap
+    /// the inner writer. Fails if the input ended in the middle of a character.
+    pub fn finish(mut self) -> io::Result<W> {
+        if !self.pending.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "input ended in the middle of a UTF-8 sequence",
+            ));
+        }
+        try!(self.write_utf16(""));
+        try!(self.inner.flush());
+        Ok(self.inner)
+    }
+
+    fn write_utf16(&mut self, text: &str) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(text.len() * 2 + 2);
+        let bom = if self.bom_written { None } else { Some(0xFEFF) };
+        for unit in bom.into_iter().chain(text.encode_utf16()) {
+            let (high, low) = ((unit >> 8) as u8, unit as u8);
+            if self.big_endian {
+                bytes.extend_from_slice(&[high, low]);
+            } else {
+                bytes.extend_from_slice(&[low, high]);
+            }
+        }
+        self.bom_written = true;
+        self.inner.write_all(&bytes)
+    }
+}
+
+impl<W: Write> Write for Utf16Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut bytes = ::std::mem::replace(&mut self.pending, vec![]);
+        bytes.extend_from_slice(buf);
+        let valid = match str::from_utf8(&bytes) {
+            Ok(_) => bytes.len(),
+            Err(e) => match e.error_len() {
+                None => e.valid_up_to(),
+                Some(_) => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "Utf16Writer input is not UTF-8",
+                    ))
+                },
+            },
+        };
+        try!(self.write_utf16(str::from_utf8(&bytes[..valid]).unwrap()));
+        self.pending = bytes[valid..].to_vec();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer that writes everything to two writers, e.g. to send a document to a
+/// client while keeping a copy for an audit log.
+///
+/// Each write goes to `first` and then to `second`, in full. If `first` fails,
+/// nothing is written to `second`.
+pub struct TeeWriter<A: Write, B: Write> {
+    first: A,
+    second: B,
+}
+
+impl<A: Write, B: Write> TeeWriter<A, B> {
+    /// Write everything to `first`, then to `second`.
+    pub fn new(first: A, second: B) -> Self {
+        TeeWriter {
+            first: first,
+            second: second,
+        }
+    }
+
+    /// References to both writers.
+    pub fn get_ref(&self) -> (&A, &B) {
+        (&self.first, &self.second)
+    }
+
+    /// Unwrap both writers.
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: Write, B: Write> Write for TeeWriter<A, B> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        try!(self.first.write_all(buf));
+        try!(self.second.write_all(buf));
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        try!(self.first.flush());
+        self.second.flush()
+    }
+}
+
+/// A writer that keeps a 64-bit FNV-1a hash of what is written through it, in
+/// segments: `take_hash` returns the hash of everything written since the last
+/// call and starts a new segment.
+pub struct HashingWriter<W: Write> {
+    inner: W,
+    hash: u64,
+    len: usize,
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+impl<W: Write> HashingWriter<W> {
+    /// Hash what is written to `inner`.
+    pub fn new(inner: W) -> Self {
+        HashingWriter {
+            inner: inner,
+            hash: FNV_OFFSET_BASIS,
+            len: 0,
+        }
+    }
+
+    /// A reference to the inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwrap the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// The number of bytes written in the current segment.
+    pub fn segment_len(&self) -> usize {
+        self.len
+    }
+
+    /// The hash of the current segment. Starts a new one.
+    pub fn take_hash(&mut self) -> u64 {
+        let hash = self.hash;
+        self.hash = FNV_OFFSET_BASIS;
+        self.len = 0;
+        hash
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = try!(self.inner.write(buf));
+        for &b in &buf[..n] {
+            self.hash = (self.hash ^ b as u64).wrapping_mul(FNV_PRIME);
+        }
+        self.len += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer that counts the bytes written through it. Use a `NullWriter` to
+/// measure output without keeping it.
+pub struct CountingWriter<W: Write> {
+    inner: W,
+    count: usize,
+}
+
+impl<W: Write> CountingWriter<W> {
+    /// Count what is written to `inner`, starting from zero.
+    pub fn new(inner: W) -> Self {
+        CountingWriter {
+            inner: inner,
+            count: 0,
+        }
+    }
+
+    /// The number of bytes written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Unwrap the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = try!(self.inner.write(buf));
+        self.count += n;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer that discards everything written to it and counts the bytes, to
+/// measure the size of the output without allocating. Pass it to `serialize`
+/// as `&mut NullWriter` to read the count afterwards.
+#[derive(Clone, Debug, Default)]
+pub struct NullWriter {
+    count: usize,
+}
+
+impl NullWriter {
+    /// A writer with a count of zero.
+    pub fn new() -> Self {
+        NullWriter { count: 0 }
+    }
+
+    /// The number of bytes written so far.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+}
+
+impl Write for NullWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.count += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -11,7 +11,7 @@
 
 //! The HTML5 tree builder.
 
-pub use interface::{create_element, ElementFlags, NextParserState, Tracer, TreeSink};
+pub use interface::{create_element, create_element_as_written, ElementFlags, NextParserState, Tracer, TreeSink};
 pub use interface::{AppendNode, AppendText, Attribute, NodeOrText};
 pub use interface::{LimitedQuirks, NoQuirks, Quirks, QuirksMode};
 
@@ -939,7 +939,7 @@ where
             // FIXME: Is there a way to avoid cloning the attributes twice here (once on their own,
             // once as part of t.clone() above)?
             let new_element =
-                self.insert_element(Push, ns!(html), tag.name.clone(), tag.attrs.clone());
+                self.insert_element(Push, ns!(html), tag.name.clone(), tag.attrs.clone(), false);
             self.active_formatting[entry_index] = Element(new_element, tag);
             if entry_index == self.active_formatting.len() - 1 {
                 break;
@@ -1280,6 +1280,7 @@ where
         ns: Namespace,
         name: LocalName,
         attrs: Vec<Attribute>,
+        self_closing: bool,
     ) -> Handle {
         declare_tag_set!(form_associatable =
             "button" "fieldset" "input" "object"
@@ -1289,7 +1290,8 @@ where
 
         // Step 7.
         let qname = QualName::new(None, ns, name);
-        let elem = create_element(&mut self.sink, qname.clone(), attrs.clone());
+        let elem =
+            create_element_as_written(&mut self.sink, qname.clone(), attrs.clone(), self_closing);
 
         let insertion_point = self.appropriate_place_for_insertion(None);
         let (node1, node2) = match insertion_point {
@@ -1328,15 +1330,15 @@ where
     }
 
     fn insert_element_for(&mut self, tag: Tag) -> Handle {
-        self.insert_element(Push, ns!(html), tag.name, tag.attrs)
+        self.insert_element(Push, ns!(html), tag.name, tag.attrs, tag.self_closing)
     }
 
     fn insert_and_pop_element_for(&mut self, tag: Tag) -> Handle {
-        self.insert_element(NoPush, ns!(html), tag.name, tag.attrs)
+        self.insert_element(NoPush, ns!(html), tag.name, tag.attrs, tag.self_closing)
     }
 
     fn insert_phantom(&mut self, name: LocalName) -> Handle {
-        self.insert_element(Push, ns!(html), name, vec![])
+        self.insert_element(Push, ns!(html), name, vec![], false)
     }
     //§ END
 
@@ -1356,7 +1358,13 @@ where
                 .remove(first_match.expect("matches with no index"));
         }
 
-        let elem = self.insert_element(Push, ns!(html), tag.name.clone(), tag.attrs.clone());
+        let elem = self.insert_element(
+            Push,
+            ns!(html),
+            tag.name.clone(),
+            tag.attrs.clone(),
+            tag.self_closing,
+        );
         self.active_formatting.push(Element(elem.clone(), tag));
         elem
     }
@@ -1488,10 +1496,10 @@ where
         self.adjust_foreign_attributes(&mut tag);
 
         if tag.self_closing {
-            self.insert_element(NoPush, ns, tag.name, tag.attrs);
+            self.insert_element(NoPush, ns, tag.name, tag.attrs, true);
             DoneAckSelfClosing
         } else {
-            self.insert_element(Push, ns, tag.name, tag.attrs);
+            self.insert_element(Push, ns, tag.name, tag.attrs, false);
             Done
         }
     }
@@ -1653,10 +1661,10 @@ where
         self.adjust_foreign_attributes(&mut tag);
         if tag.self_closing {
             // FIXME(#118): <script /> in SVG
-            self.insert_element(NoPush, current_ns, tag.name, tag.attrs);
+            self.insert_element(NoPush, current_ns, tag.name, tag.attrs, true);
             DoneAckSelfClosing
         } else {
-            self.insert_element(Push, current_ns, tag.name, tag.attrs);
+            self.insert_element(Push, current_ns, tag.name, tag.attrs, false);
             Done
         }
     }
@@ -7,19 +7,39 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+#[cfg(feature = "bytes")]
+extern crate bytes;
 #[macro_use]
 extern crate html5ever;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+extern crate xml5ever;
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::default::Default;
 
 use html5ever::driver::ParseOpts;
-use html5ever::rcdom::RcDom;
-use html5ever::serialize::{Serialize, SerializeOpts, Serializer, TraversalScope};
+use html5ever::rcdom::{Handle, NodeData, RcDom};
+use html5ever::serialize::writers::{EscapingWriter, TeeWriter, Utf16Writer};
+use html5ever::serialize::{
+    escape_attribute_value, serialize_as_attribute_value, serialize_cancellable, serialize_chunked,
+    serialize_json, serialize_markdown, serialize_skipping, serialize_summarized,
+    serialize_text_content, serialize_text_index, serialize_to_string_exact,
+    serialize_to_string_with_capacity, serialize_tokens, serialize_with_boundaries,
+    serialize_with_sourcemap, serialize_without_tracking, serialize_wrapped, text_mode,
+    BidiControls, Cancelled, Diagnostic, DuplicateIds, ElementHook, EntitySpan, EntityStyle,
+    HtmlSerializer, LongAttributeValue, NonAsciiError, NullWriter, OutputSyntax, Serialize,
+    SerializeOpts, Serializer, StrayText, StripTrackingSerializer, StyleSanitizer,
+    TextIndexSerializer, TextMode, TextOnlySerializer, TokenSerializer, TraversalScope,
+    ValidatingSerializer, WhitespaceText,
+};
 use html5ever::tendril::{SliceExt, StrTendril, TendrilSink};
 use html5ever::tokenizer::{TagKind, Token, TokenSink, TokenSinkResult, Tokenizer};
-use html5ever::{parse_document, parse_fragment, serialize, QualName};
+use html5ever::tree_builder::TreeBuilderOpts;
+use html5ever::{parse_document, parse_fragment, sanitize_html, serialize, LocalName, QualName};
 
 use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 struct Tokens(Vec<Token>);
 
@@ -69,6 +89,16 @@ impl Serialize for Tokens {
 }
 
 fn tokenize_and_serialize(input: StrTendril) -> StrTendril {
+    tokenize_and_serialize_with(
+        input,
+        SerializeOpts {
+            create_missing_parent: true,
+            ..SerializeOpts::spec_compliant()
+        },
+    )
+}
+
+fn tokenize_and_serialize_with(input: StrTendril, opts: SerializeOpts) -> StrTendril {
     let mut input = {
         let mut q = ::html5ever::tokenizer::BufferQueue::new();
         q.push_front(input.into());
@@ -78,19 +108,20 @@ fn tokenize_and_serialize(input: StrTendril) -> StrTendril {
     tokenizer.feed(&mut input);
     tokenizer.end();
     let mut output = ::std::io::Cursor::new(vec![]);
-    serialize(
-        &mut output,
-        &tokenizer.sink,
-        SerializeOpts {
-            create_missing_parent: true,
-            ..Default::default()
-        },
-    )
-    .unwrap();
+    serialize(&mut output, &tokenizer.sink, opts).unwrap();
     StrTendril::try_from_byte_slice(&output.into_inner()).unwrap()
 }
 
 fn parse_and_serialize(input: StrTendril) -> StrTendril {
+    parse_and_serialize_with(input, SerializeOpts::spec_compliant())
+}
+
+/// Like `parse_and_serialize`, with the default, sanitizing options.
+fn sanitize(input: StrTendril) -> StrTendril {
+    parse_and_serialize_with(input, Default::default())
+}
+
+fn parse_and_serialize_with(input: StrTendril, opts: SerializeOpts) -> StrTendril {
     let dom = parse_fragment(
         RcDom::default(),
         ParseOpts::default(),
@@ -101,10 +132,28 @@ fn parse_and_serialize(input: StrTendril) -> StrTendril {
     let inner = &dom.document.children.borrow()[0];
 
     let mut result = vec![];
-    serialize(&mut result, inner, Default::default()).unwrap();
+    serialize(&mut result, inner, opts).unwrap();
     StrTendril::try_from_byte_slice(&result).unwrap()
 }
 
+/// A lone comment node, for text the parser could never produce.
+struct Comment(&'static str);
+
+impl Serialize for Comment {
+    fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        serializer.write_comment(self.0)
+    }
+}
+
+fn serialize_to_string<T: Serialize>(node: &T, opts: SerializeOpts) -> String {
+    let mut result = vec![];
+    serialize(&mut result, node, opts).unwrap();
+    String::from_utf8(result).unwrap()
+}
+
 macro_rules! test_fn {
     ($f:ident, $name:ident, $input:expr, $output:expr) => {
         #[test]
@@ -226,39 +275,3037 @@ test!(comment_1, r#"<p>hi <!--world--></p>"#);
 test!(comment_2, r#"<p>hi <!-- world--></p>"#);
 test!(comment_3, r#"<p>hi <!--world --></p>"#);
 test!(comment_4, r#"<p>hi <!-- world --></p>"#);
+test!(comment_double_dash_raw, r#"<p><!-- a -- b --></p>"#);
+test_fn!(
+    sanitize,
+    comment_double_dash,
+    r#"<p><!-- a -- b --></p>"#,
+    r#"<p><!-- a - - b --></p>"#
+);
 
-// FIXME: test serialization of qualified tag/attribute names that can't be
-// parsed from HTML
+#[test]
+fn comment_breakout_is_escaped() {
+    assert_eq!(
+        serialize_to_string(&Comment("--><script>x</script>"), Default::default()),
+        "<!--- -><script>x</script>-->"
+    );
+    assert_eq!(
+        serialize_to_string(&Comment(">x"), Default::default()),
+        "<!-- >x-->"
+    );
+}
 
-test!(attr_ns_1, r#"<svg xmlns="bleh"></svg>"#);
-test!(attr_ns_2, r#"<svg xmlns:foo="bleh"></svg>"#);
-test!(attr_ns_3, r#"<svg xmlns:xlink="bleh"></svg>"#);
-test!(attr_ns_4, r#"<svg xlink:href="bleh"></svg>"#);
+#[test]
+fn nested_comment_is_escaped() {
+    assert_eq!(
+        serialize_to_string(&Comment("a<!--b-->c"), Default::default()),
+        "<!--a<! - -b- ->c-->"
+    );
+    assert_eq!(
+        serialize_to_string(&Comment("a<!-"), Default::default()),
+        "<!--a<! --->"
+    );
+    // It is read back as a single comment.
+    let output = serialize_to_string(&Comment("a<!--b-->c"), Default::default());
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one(output);
+    let root = &dom.document.children.borrow()[0];
+    let children = root.children.borrow();
+    assert_eq!(children.len(), 1);
+    match children[0].data {
+        NodeData::Comment { ref contents } => assert_eq!(&**contents, "a<! - -b- ->c"),
+        _ => panic!("not a comment"),
+    }
+}
 
-test_no_parse!(malformed_tokens, r#"foo</div><div>"#);
+#[test]
+fn preserve_raw_comments() {
+    let opts = SerializeOpts {
+        preserve_raw_comments: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with("<p><!-- {{#if a -- b}} --></p>".to_tendril(), opts.clone()),
+        "<p><!-- {{#if a -- b}} --></p>"
+    );
+    assert_eq!(serialize_to_string(&Comment("a--b"), opts), "<!--a--b-->");
+}
 
 #[test]
-fn doctype() {
-    let dom = parse_document(RcDom::default(), ParseOpts::default()).one("<!doctype html>");
-    dom.document.children.borrow_mut().truncate(1); // Remove <html>
+fn always_allowed_attributes() {
+    let mut allowed = BTreeMap::new();
+    allowed.insert(
+        local_name!("p"),
+        vec![local_name!("class")]
+            .into_iter()
+            .collect::<BTreeSet<_>>(),
+    );
+    let opts = SerializeOpts {
+        allowed_attributes: Some(allowed),
+        ..Default::default()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<p lang="fr" class="x" onclick="y"><em dir="rtl" style="z">a</em></p>"#.to_tendril(),
+            opts.clone()
+        ),
+        r#"<p lang="fr" class="x"><em dir="rtl">a</em></p>"#
+    );
+
+    let opts = SerializeOpts {
+        always_allowed_attributes: BTreeSet::new(),
+        ..opts
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(r#"<p lang="fr" class="x">a</p>"#.to_tendril(), opts),
+        r#"<p class="x">a</p>"#
+    );
+}
+
+fn polyglot(input: StrTendril) -> StrTendril {
+    parse_and_serialize_with(
+        input,
+        SerializeOpts {
+            allowed_tags: None,
+            ..SerializeOpts::polyglot()
+        },
+    )
+}
+
+test_fn!(
+    polyglot,
+    polyglot_void_elements,
+    r#"<p>a<br><img src="a.png" alt=""></p>"#,
+    r#"<p>a<br /><img src="a.png" alt="" /></p>"#
+);
+test_fn!(
+    polyglot,
+    polyglot_nbsp,
+    "<p title=\"x\u{a0}y\">x\u{a0}y</p>",
+    r#"<p title="x&#xa0;y">x&#xa0;y</p>"#
+);
+test_fn!(polyglot, polyglot_plain_script, "<script>f(a, b)</script>");
+test_fn!(
+    polyglot,
+    polyglot_script_cdata,
+    "<script>if (a < b && c) {}</script>",
+    "<script>//<![CDATA[\nif (a < b && c) {}\n//]]></script>"
+);
+test_fn!(
+    polyglot,
+    polyglot_style_cdata,
+    r#"<style>a[title="&"] {}</style>"#,
+    r#"<style>/*<![CDATA[*/a[title="&"] {}/*]]>*/</style>"#
+);
+test_fn!(
+    polyglot,
+    polyglot_svg_namespace,
+    r#"<svg><circle r="1"></circle></svg>"#,
+    r#"<svg xmlns="http://www.w3.org/2000/svg"><circle r="1"></circle></svg>"#
+);
+
+#[test]
+fn foreign_object() {
+    let input = "<svg><foreignObject><p>x<br>y</p></foreignObject><rect></rect></svg><p>z</p>";
+    assert_eq!(&*parse_and_serialize(input.to_tendril()), input);
+
+    let opts = SerializeOpts {
+        allowed_tags: None,
+        ..SerializeOpts::polyglot()
+    };
+    let output = parse_and_serialize_with(input.to_tendril(), opts.clone());
+    assert_eq!(
+        &*output,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\"><foreignObject>\
+         <p xmlns=\"http://www.w3.org/1999/xhtml\">x<br />y</p></foreignObject><rect></rect></svg>\
+         <p>z</p>"
+    );
+    // Reading it as XML gives the same namespaces.
+    let dom = xml5ever::driver::parse_document(RcDom::default(), Default::default()).one(format!(
+        "<div xmlns=\"http://www.w3.org/1999/xhtml\">{}</div>",
+        output
+    ));
+    let mut tree = String::new();
+    dump_tree(&dom.document.children.borrow()[0], &mut tree);
+    let svg = "{http://www.w3.org/2000/svg}";
+    let html = "{http://www.w3.org/1999/xhtml}";
+    assert_eq!(
+        tree,
+        format!(
+            "<{h}div><{s}svg><{s}foreignObject><{h}p>x/<{h}br>/y///<{s}rect>//<{h}p>z///",
+            h = html,
+            s = svg
+        )
+    );
+    // And the HTML parser ignores the declarations.
+    assert_eq!(&*parse_and_serialize_with(output.clone(), opts), &*output);
+}
+
+#[test]
+fn polyglot_document() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<!DOCTYPE html><html lang=en><head><title>t</title></head><body></body></html>");
+    let opts = SerializeOpts {
+        allowed_tags: None,
+        ..SerializeOpts::polyglot()
+    };
+    assert_eq!(
+        serialize_to_string(&dom.document, opts),
+        "<!DOCTYPE html><html xmlns=\"http://www.w3.org/1999/xhtml\" lang=\"en\">\
+         <head><title>t</title></head><body></body></html>"
+    );
+}
+
+#[test]
+fn output_syntax() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<!DOCTYPE html><script>if (a < b && c) {}</script><p>x<br>y&nbsp;z</p>");
+    let serialize_as = |syntax| {
+        let opts = SerializeOpts {
+            output_syntax: syntax,
+            ..SerializeOpts::spec_compliant()
+        };
+        serialize_to_string(&dom.document, opts)
+    };
+    assert_eq!(
+        serialize_as(OutputSyntax::Html),
+        "<!DOCTYPE html><html><head><script>if (a < b && c) {}</script></head>\
+         <body><p>x<br>y&nbsp;z</p></body></html>"
+    );
+    assert_eq!(
+        serialize_as(OutputSyntax::Xhtml),
+        "<!DOCTYPE html><html xmlns=\"http://www.w3.org/1999/xhtml\"><head>\
+         <script>//<![CDATA[\nif (a < b && c) {}\n//]]></script></head>\
+         <body><p>x<br />y&#xa0;z</p></body></html>"
+    );
+    assert_eq!(
+        serialize_as(OutputSyntax::Xml),
+        "<!DOCTYPE html><html xmlns=\"http://www.w3.org/1999/xhtml\"><head>\
+         <script>if (a &lt; b &amp;&amp; c) {}</script></head>\
+         <body><p>x<br />y&#xa0;z</p></body></html>"
+    );
+}
+
+#[test]
+fn ascii_only() {
+    let opts = SerializeOpts {
+        ascii_only: true,
+        ..Default::default()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<p title=\"caf\u{e9}\">\u{1f600} &amp; \u{a0}</p>".to_tendril(),
+            opts
+        ),
+        r#"<p title="caf&#xe9;">&#x1f600; &amp; &nbsp;</p>"#
+    );
+}
+
+#[test]
+fn strict_ascii() {
+    let opts = SerializeOpts {
+        ascii_only: true,
+        strict_ascii: true,
+        ..Default::default()
+    };
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<p>ok</p><p>caf\u{e9}</p>");
     let mut result = vec![];
-    serialize(&mut result, &dom.document, Default::default()).unwrap();
-    assert_eq!(String::from_utf8(result).unwrap(), "<!DOCTYPE html>");
+    let err = serialize(&mut result, &dom.document.children.borrow()[0], opts).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<NonAsciiError>(),
+        Some(&NonAsciiError {
+            character: '\u{e9}',
+            offset: 3,
+        })
+    );
+    assert_eq!(String::from_utf8(result).unwrap(), "<p>ok</p><p>");
+
+    // Attributes that are dropped are never checked, and values that the
+    // options add are.
+    let opts = SerializeOpts {
+        ascii_only: true,
+        strict_ascii: true,
+        strip_event_handlers: true,
+        link_rel: Some("n\u{e9}".to_string()),
+        ..Default::default()
+    };
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<p onclick=\"caf\u{e9}()\">ok</p><a href=\"/\">x</a>");
+    let mut result = vec![];
+    let err = serialize(&mut result, &dom.document.children.borrow()[0], opts).unwrap_err();
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<NonAsciiError>(),
+        Some(&NonAsciiError {
+            character: '\u{e9}',
+            offset: 1,
+        })
+    );
+    assert_eq!(
+        String::from_utf8(result).unwrap(),
+        "<p>ok</p><a href=\"/\" rel"
+    );
+}
+
+fn minimize_empty_attributes(input: StrTendril) -> StrTendril {
+    let opts = SerializeOpts {
+        allowed_tags: None,
+        minimize_empty_attributes: true,
+        ..Default::default()
+    };
+    parse_and_serialize_with(input, opts)
+}
+
+test_fn!(
+    minimize_empty_attributes,
+    minimize_empty_alt,
+    r#"<img src="a.png" alt="">"#,
+    r#"<img src="a.png" alt>"#
+);
+test_fn!(
+    minimize_empty_attributes,
+    minimize_keeps_empty_value,
+    r#"<input value="" disabled="">"#,
+    r#"<input value="" disabled>"#
+);
+
+fn rcdata(input: StrTendril) -> StrTendril {
+    let opts = SerializeOpts {
+        allowed_tags: None,
+        ..Default::default()
+    };
+    parse_and_serialize_with(input, opts)
 }
 
+test_fn!(
+    rcdata,
+    rcdata_title_lt,
+    "<title>a<b</title>",
+    "<title>a&lt;b</title>"
+);
+test_fn!(
+    rcdata,
+    rcdata_textarea,
+    "<textarea>a>b&amp;c</textarea>",
+    "<textarea>a&gt;b&amp;c</textarea>"
+);
+test_fn!(
+    rcdata,
+    rcdata_end_tag_text,
+    "<title>&lt;/title&gt;<p>x</title>",
+    "<title>&lt;/title&gt;&lt;p&gt;x</title>"
+);
+
 #[test]
-fn deep_tree() {
-    let parser = parse_fragment(
+fn rcdata_round_trip() {
+    for input in &[
+        "<title>a<b</title>",
+        "<textarea>&lt;/textarea&gt;</textarea>",
+    ] {
+        let once = rcdata(StrTendril::from_slice(input));
+        let twice = rcdata(once.clone());
+        assert_eq!(once, twice);
+    }
+}
+
+fn rcdata_literal_gt(input: StrTendril) -> StrTendril {
+    let opts = SerializeOpts {
+        allowed_tags: None,
+        escape_gt_in_rcdata: false,
+        ..Default::default()
+    };
+    parse_and_serialize_with(input, opts)
+}
+
+test_fn!(
+    rcdata_literal_gt,
+    rcdata_gt_unescaped,
+    "<textarea>&lt;/textarea&gt; a>b&amp;c</textarea>",
+    "<textarea>&lt;/textarea> a>b&amp;c</textarea>"
+);
+
+#[test]
+fn skip_internal_elements() {
+    let dom = parse_fragment(
         RcDom::default(),
         ParseOpts::default(),
-        QualName::new(None, ns!(html), local_name!("div")),
+        QualName::new(None, ns!(html), local_name!("body")),
         vec![],
+    )
+    .one(
+        "<p>a<span data-internal>secret<b>x</b><!--c--></span>b</p><div data-internal=1>gone</div>",
     );
-    let src = String::from("<b>".repeat(60_000));
-    let dom = parser.one(src);
-    let document = &dom.document;
-    let opts = SerializeOpts::default();
-    let mut ret_val = Vec::new();
-    serialize(&mut ret_val, document, opts)
-        .expect("Writing to a string shouldn't fail (expect on OOM)");
+    let opts = SerializeOpts {
+        allowed_tags: None,
+        ..Default::default()
+    };
+    let mut result = vec![];
+    serialize_skipping(
+        &mut result,
+        &dom.document.children.borrow()[0],
+        opts,
+        |_, attrs| {
+            attrs
+                .iter()
+                .any(|&(name, _)| &*name.local == "data-internal")
+        },
+    )
+    .unwrap();
+    assert_eq!(String::from_utf8(result).unwrap(), "<p>ab</p>");
+}
+
+#[test]
+fn json_ast() {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<p class=\"x\">Hi <b>\"there\"</b><br></p><!--c-->");
+    let mut result = vec![];
+    serialize_json(
+        &mut result,
+        &dom.document.children.borrow()[0],
+        TraversalScope::ChildrenOnly(None),
+    )
+    .unwrap();
+    let expected = r#"[
+  {
+    "type": "element",
+    "name": "p",
+    "attributes": {
+      "class": "x"
+    },
+    "children": [
+      {
+        "type": "text",
+        "value": "Hi "
+      },
+      {
+        "type": "element",
+        "name": "b",
+        "attributes": {},
+        "children": [
+          {
+            "type": "text",
+            "value": "\"there\""
+          }
+        ]
+      },
+      {
+        "type": "element",
+        "name": "br",
+        "attributes": {},
+        "children": []
+      }
+    ]
+  },
+  {
+    "type": "comment",
+    "value": "c"
+  }
+]"#;
+    assert_eq!(String::from_utf8(result).unwrap(), expected);
+}
+
+#[test]
+fn into_inner() {
+    let mut ser = HtmlSerializer::new(vec![], Default::default());
+    Comment("hi")
+        .serialize(&mut ser, TraversalScope::IncludeNode)
+        .unwrap();
+    assert_eq!(ser.get_ref(), b"<!--hi-->");
+    ser.get_mut().clear();
+    Comment("there")
+        .serialize(&mut ser, TraversalScope::IncludeNode)
+        .unwrap();
+    assert_eq!(ser.into_inner(), b"<!--there-->");
+}
+
+test_fn!(
+    sanitize,
+    escaped_tag_name,
+    "<div<img src=x onerror=alert(1)>>",
+    "&lt;div&lt;img src=&quot;x&quot; onerror=&quot;alert(1)&quot;&gt;&gt;&lt;/div&lt;img&gt;"
+);
+test_fn!(
+    sanitize,
+    escaped_attr_value,
+    r#"<div title="<script>x</script>"></div>"#,
+    "&lt;div title=&quot;&lt;script&gt;x&lt;/script&gt;&quot;&gt;&lt;/div&gt;"
+);
+test_fn!(
+    sanitize,
+    escaped_raw_text,
+    "<xmp><script>x</script></xmp>",
+    "&lt;xmp&gt;&lt;script&gt;x&lt;/script&gt;&lt;/xmp&gt;"
+);
+
+fn ugc(input: StrTendril) -> StrTendril {
+    parse_and_serialize_with(input, SerializeOpts::ugc())
+}
+
+test_fn!(
+    ugc,
+    ugc_comment,
+    r#"<p>Nice <a href="https://example.com/x" rel="me" onclick="x()">link</a>, see <img src="/a.png" alt="pic" style="x"> and <code>a &lt; b</code></p><ol start=3><li><b>bold</b></ol>"#,
+    r#"<p>Nice <a href="https://example.com/x" rel="nofollow noopener">link</a>, see <img src="/a.png" alt="pic"> and <code>a &lt; b</code></p><ol start="3"><li><b>bold</b></li></ol>"#
+);
+test_fn!(
+    ugc,
+    ugc_xss,
+    r#"<p id=x><a href=" java&#9;script:alert(1)">x</a><img src=x onerror=alert(1)><a href="data:text/html,x">y</a><script>alert("<b>")</script></p>"#,
+    r#"<p><a rel="nofollow noopener">x</a><img src="x"><a rel="nofollow noopener">y</a>&lt;script&gt;alert("&lt;b&gt;")&lt;/script&gt;</p>"#
+);
+
+fn base_url(input: StrTendril) -> StrTendril {
+    let opts = SerializeOpts {
+        allowed_tags: None,
+        base_url: Some("https://x/a/b/".to_string()),
+        ..Default::default()
+    };
+    parse_and_serialize_with(input, opts)
+}
+
+test_fn!(
+    base_url,
+    base_url_parent_dir,
+    r#"<img src="../img.png" alt="../x"><img src="é/../ü">"#,
+    r#"<img src="https://x/a/img.png" alt="../x"><img src="https://x/a/b/ü">"#
+);
+test_fn!(
+    base_url,
+    base_url_references,
+    r##"<a href="c?q#f"></a><a href="/d/./e/../f"></a><a href="//y/z"></a><a href="?q"></a><a href="#top"></a><a href="mailto:a@x"></a>"##,
+    r##"<a href="https://x/a/b/c?q#f"></a><a href="https://x/d/f"></a><a href="https://y/z"></a><a href="https://x/a/b/?q"></a><a href="https://x/a/b/#top"></a><a href="mailto:a@x"></a>"##
+);
+
+#[test]
+fn attr_name_map() {
+    let mut opts = SerializeOpts {
+        allowed_tags: None,
+        ..Default::default()
+    };
+    opts.attr_name_map.insert(
+        QualName::new(None, ns!(), LocalName::from("ng-click")),
+        QualName::new(None, ns!(), LocalName::from("data-ng-click")),
+    );
+    opts.attr_name_map.insert(
+        QualName::new(None, ns!(), LocalName::from("href")),
+        QualName::new(None, ns!(xlink), LocalName::from("href")),
+    );
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r##"<a ng-click="go(1) && stop()" href="#x">go</a>"##.to_tendril(),
+            opts
+        ),
+        r##"<a data-ng-click="go(1) &amp;&amp; stop()" xlink:href="#x">go</a>"##
+    );
+}
+
+#[test]
+fn script_nonce() {
+    let mut opts = SerializeOpts {
+        allowed_tags: None,
+        script_nonce: Some("r4nd\"0m".to_string()),
+        ..Default::default()
+    };
+    let input =
+        r#"<p>x</p><script nonce=old>go()</script><style>p {}</style><script src=a.js></script>"#;
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts.clone()),
+        r#"<p>x</p><script nonce="r4nd&quot;0m">go()</script><style>p {}</style><script src="a.js" nonce="r4nd&quot;0m"></script>"#
+    );
+    opts.style_nonce = true;
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        r#"<p>x</p><script nonce="r4nd&quot;0m">go()</script><style nonce="r4nd&quot;0m">p {}</style><script src="a.js" nonce="r4nd&quot;0m"></script>"#
+    );
+}
+
+#[test]
+fn text_content() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default()).one(
+        "<!DOCTYPE html><title>T</title><div><p>One <b>two</b></p><!--no--><ul><li>three<li>four</ul>five</div>",
+    );
+    let html = &dom.document.children.borrow()[1];
+    assert_eq!(serialize_text_content(html), "TOne twothreefourfive");
+
+    let mut ser = TextOnlySerializer::new(true);
+    html.serialize(&mut ser, TraversalScope::ChildrenOnly(None))
+        .unwrap();
+    assert_eq!(ser.into_string(), "T One two three four five");
+}
+
+struct BrWithText;
+
+impl Serialize for BrWithText {
+    fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        let br = QualName::new(None, ns!(html), local_name!("br"));
+        serializer.start_elem(br.clone(), vec![].into_iter())?;
+        serializer.write_text("oops")?;
+        serializer.end_elem(br)
+    }
+}
+
+#[test]
+fn strict_void() {
+    assert_eq!(serialize_to_string(&BrWithText, Default::default()), "<br>");
+
+    let opts = SerializeOpts {
+        strict_void: true,
+        ..Default::default()
+    };
+    let err = serialize(vec![], &BrWithText, opts).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "void element <br> has children");
+}
+
+test_fn!(
+    rcdata,
+    listing_round_trip,
+    "<listing>a &lt;b&gt; &amp; c</listing>"
+);
+
+#[test]
+fn extra_raw_text_elements() {
+    let mut opts = SerializeOpts {
+        allowed_tags: None,
+        ..Default::default()
+    };
+    opts.extra_raw_text_elements
+        .insert(LocalName::from("x-raw"));
+    assert_eq!(
+        &*parse_and_serialize_with("<x-raw>a &lt; b</x-raw><p>a &lt; b</p>".to_tendril(), opts),
+        "<x-raw>a < b</x-raw><p>a &lt; b</p>"
+    );
+}
+
+#[test]
+fn debug_is_deterministic() {
+    let schemes = |list: &[&str]| SerializeOpts {
+        allowed_url_schemes: Some(list.iter().map(|s| s.to_string()).collect()),
+        ..SerializeOpts::ugc()
+    };
+    let a = format!("{:?}", schemes(&["https", "mailto", "http"]));
+    let b = format!("{:?}", schemes(&["mailto", "http", "https"]));
+    assert_eq!(a, b);
+    assert_eq!(a, format!("{:?}", SerializeOpts::ugc()));
+    assert!(a.contains(r#"allowed_url_schemes: Some({"http", "https", "mailto"})"#));
+}
+
+#[test]
+fn opts_eq() {
+    let tags = [
+        "p",
+        "br",
+        "strong",
+        "em",
+        "del",
+        "blockquote",
+        "code",
+        "pre",
+        "h1",
+        "h2",
+        "h3",
+        "h4",
+        "h5",
+        "h6",
+        "a",
+        "ul",
+        "ol",
+        "li",
+        "hr",
+    ];
+    let manual = SerializeOpts {
+        scripting_enabled: true,
+        serialize_noscript_as_elements: None,
+        traversal_scope: TraversalScope::ChildrenOnly(None),
+        omit_html_body_tags: false,
+        wrap_bare_text: false,
+        create_missing_parent: false,
+        repair_tree: false,
+        preserve_raw_comments: false,
+        allowed_tags: Some(tags.iter().map(|&t| LocalName::from(t)).collect()),
+        allowed_attributes: None,
+        always_allowed_attributes: ["lang", "dir", "title", "id"]
+            .iter()
+            .map(|&a| LocalName::from(a))
+            .collect(),
+        strip_event_handlers: false,
+        output_syntax: OutputSyntax::Html,
+        ascii_only: false,
+        strict_ascii: false,
+        minimize_empty_attributes: false,
+        minimal_amp_escaping: false,
+        escape_gt_in_rcdata: true,
+        allowed_url_schemes: None,
+        link_rel: None,
+        base_url: None,
+        src_prefix: None,
+        attr_name_map: BTreeMap::new(),
+        script_nonce: None,
+        style_nonce: false,
+        strict_void: false,
+        stray_text: StrayText::Keep,
+        extra_raw_text_elements: BTreeSet::new(),
+        preserved_comment_exact: None,
+        tag_rewrite: BTreeMap::new(),
+        max_attributes_per_element: None,
+        max_total_elements: None,
+        max_attribute_value_len: None,
+        long_attribute_values: LongAttributeValue::Truncate,
+        whitespace_text: WhitespaceText::Preserve,
+        escape_nbsp: true,
+        mirror_lang_to_xml: false,
+        lossless_namespaces: false,
+        lowercase_value_attrs: BTreeSet::new(),
+        canonicalize_token_attrs: BTreeSet::new(),
+        attribute_order_priority: vec![],
+        allowed_is_values: None,
+        allowed_script_srcs: None,
+        lazy_load_images: false,
+        numeric_reference_ranges: vec![],
+        bidi_controls: BidiControls::Keep,
+        hex_uppercase: false,
+        entity_style: EntityStyle::Minimal,
+        disallowed_replacement: None,
+        pretty_print: false,
+        indent_with: "  ".to_string(),
+        no_indent_children: BTreeSet::new(),
+        max_line_length: None,
+        inline_elements: SerializeOpts::default().inline_elements,
+        preserve_self_closing: false,
+        drop_default_attributes: false,
+        editor_markers: BTreeSet::new(),
+        #[cfg(feature = "unicode-normalization")]
+        normalize_nfc: false,
+        style_sanitizer: None,
+        duplicate_ids: DuplicateIds::Keep,
+        ensure_charset: false,
+        doctype_only_in_no_quirks: false,
+        element_hook: None,
+    };
+    assert_eq!(manual, SerializeOpts::default());
+    assert!(SerializeOpts::polyglot() != SerializeOpts::default());
+}
+
+#[test]
+fn chunked() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<!DOCTYPE html><title>Chunks</title><p>Some <b>text</b> in caf\u{e9}s</p>");
+    let mut full = vec![];
+    serialize(&mut full, &dom.document, SerializeOpts::ugc()).unwrap();
+
+    for &size in &[1, 7, 16, 1000] {
+        let mut chunks: Vec<Vec<u8>> = vec![];
+        serialize_chunked(&dom.document, SerializeOpts::ugc(), size, |chunk| {
+            chunks.push(chunk.to_vec())
+        })
+        .unwrap();
+        assert!(chunks.iter().all(|c| c.len() <= size));
+        assert!(chunks[..chunks.len() - 1].iter().all(|c| c.len() == size));
+        assert_eq!(chunks.concat(), full);
+    }
+}
+
+#[test]
+fn preserved_comment_exact() {
+    let opts = SerializeOpts {
+        preserved_comment_exact: Some(vec!["more".to_string()].into_iter().collect()),
+        ..Default::default()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<p>Intro</p><!--more--><!-- secret --><!--moreover--><p>Rest<!-- more --></p>"
+                .to_tendril(),
+            opts
+        ),
+        "<p>Intro</p><!--more--><p>Rest<!-- more --></p>"
+    );
+}
+
+#[test]
+fn tag_rewrite() {
+    let mut opts = SerializeOpts::default();
+    opts.tag_rewrite
+        .insert(local_name!("b"), local_name!("strong"));
+    opts.tag_rewrite.insert(local_name!("i"), local_name!("em"));
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<p><b>bold <i>both</i> <b>twice</b></b> <i>it</i></p>".to_tendril(),
+            opts
+        ),
+        "<p><strong>bold <em>both</em> <strong>twice</strong></strong> <em>it</em></p>"
+    );
+}
+
+struct Malformed;
+
+impl Serialize for Malformed {
+    fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        let html = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+        let id = QualName::new(None, ns!(), local_name!("id"));
+        serializer.start_elem(html("p"), vec![(&id, "1"), (&id, "2")].into_iter())?;
+        serializer.start_elem(html("br"), vec![].into_iter())?;
+        serializer.write_text("x")?;
+        serializer.end_elem(html("br"))?;
+        serializer.start_elem(html("b"), vec![].into_iter())?;
+        serializer.end_elem(html("i"))?;
+        serializer.end_elem(html("p"))?;
+        serializer.end_elem(html("div"))?;
+        serializer.start_elem(html("em"), vec![].into_iter())
+    }
+}
+
+#[test]
+fn validating_serializer() {
+    let opts = SerializeOpts {
+        repair_tree: true,
+        ..Default::default()
+    };
+    let mut ser = ValidatingSerializer::new(HtmlSerializer::new(vec![], opts.clone()));
+    Malformed
+        .serialize(&mut ser, TraversalScope::IncludeNode)
+        .unwrap();
+    let (mut inner, diagnostics) = ser.into_parts();
+    inner.finish().unwrap();
+    assert_eq!(
+        inner.into_inner(),
+        serialize_to_string(&Malformed, opts).into_bytes()
+    );
+
+    let html = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    assert_eq!(
+        diagnostics,
+        vec![
+            Diagnostic::DuplicateAttribute {
+                element: html("p"),
+                attribute: QualName::new(None, ns!(), local_name!("id")),
+            },
+            Diagnostic::VoidElementWithChildren(html("br")),
+            Diagnostic::MismatchedEndTag {
+                open: html("b"),
+                end: html("i"),
+            },
+            Diagnostic::UnmatchedEndTag(html("div")),
+            Diagnostic::UnclosedElement(html("em")),
+        ]
+    );
+}
+
+#[test]
+fn validate() {
+    let html = |local: &str| QualName::new(None, ns!(html), LocalName::from(local));
+    assert_eq!(
+        HtmlSerializer::validate(&Malformed, Default::default()),
+        Err(vec![
+            Diagnostic::DuplicateAttribute {
+                element: html("p"),
+                attribute: QualName::new(None, ns!(), local_name!("id")),
+            },
+            Diagnostic::VoidElementWithChildren(html("br")),
+            Diagnostic::DisallowedElement(html("b")),
+            Diagnostic::MismatchedEndTag {
+                open: html("b"),
+                end: html("i"),
+            },
+            Diagnostic::UnmatchedEndTag(html("div")),
+            Diagnostic::UnclosedElement(html("em")),
+        ])
+    );
+
+    let dom = parse_document(RcDom::default(), ParseOpts::default()).one("<p>a<br>b</p>");
+    assert_eq!(
+        HtmlSerializer::validate(&dom.document, SerializeOpts::spec_compliant()),
+        Ok(())
+    );
+
+    let dom = xml5ever::driver::parse_document(RcDom::default(), Default::default())
+        .one(r#"<c:widget xmlns:c="urn:example:custom"/>"#);
+    assert_eq!(
+        HtmlSerializer::validate(&dom.document, SerializeOpts::spec_compliant()),
+        Err(vec![Diagnostic::UnknownNamespace(QualName::new(
+            Some("c".into()),
+            "urn:example:custom".into(),
+            "widget".into()
+        ))])
+    );
+    let opts = SerializeOpts {
+        lossless_namespaces: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(HtmlSerializer::validate(&dom.document, opts), Ok(()));
+
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one(concat!(
+        r#"<script src="https://cdn.ampproject.org/v0.js"></script>"#,
+        r#"<script src="https://evil.example/x.js"></script>"#,
+        r#"<script type="application/ld+json">{}</script>"#,
+    ));
+    let opts = SerializeOpts {
+        allowed_script_srcs: Some(vec!["https://cdn.ampproject.org/".to_string()]),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        HtmlSerializer::validate(&dom.document.children.borrow()[0], opts),
+        Err(vec![Diagnostic::DisallowedElement(html("script"))])
+    );
+}
+
+#[test]
+fn max_attributes_per_element() {
+    let attrs: String = (0..10000).map(|i| format!(" a{}={}", i, i)).collect();
+    let input = format!("<p{}>x</p>", attrs);
+    let opts = SerializeOpts {
+        max_attributes_per_element: Some(100),
+        ..Default::default()
+    };
+    let output = parse_and_serialize_with(StrTendril::from_slice(&input), opts);
+    let expected: String = (0..100).map(|i| format!(" a{}=\"{}\"", i, i)).collect();
+    assert_eq!(&*output, format!("<p{}>x</p>", expected));
+}
+
+#[test]
+fn max_total_elements() {
+    let input = "<i></i>".repeat(10000);
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one(input.clone());
+    let inner = &dom.document.children.borrow()[0];
+    let opts = |max| SerializeOpts {
+        max_total_elements: Some(max),
+        ..SerializeOpts::spec_compliant()
+    };
+
+    let mut result = vec![];
+    let err = serialize(&mut result, inner, opts(1000)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(result, "<i></i>".repeat(1000).as_bytes());
+
+    assert_eq!(serialize_to_string(inner, opts(10000)), input);
+
+    // The `<meta>` and `<p>` that the options add don't count: the document
+    // has exactly four elements.
+    let dom = parse_document(RcDom::default(), ParseOpts::default()).one("<title>t</title>x");
+    let opts = |max| SerializeOpts {
+        max_total_elements: Some(max),
+        ensure_charset: true,
+        wrap_bare_text: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        serialize_to_string(&dom.document, opts(4)),
+        "<html><head><meta charset=\"utf-8\"><title>t</title></head><body><p>x</p></body></html>"
+    );
+    let mut result = vec![];
+    assert!(serialize(&mut result, &dom.document, opts(3)).is_err());
+}
+
+struct SpacedParagraphs;
+
+impl Serialize for SpacedParagraphs {
+    fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        let p = QualName::new(None, ns!(html), local_name!("p"));
+        let pre = QualName::new(None, ns!(html), local_name!("pre"));
+        serializer.start_elem(p.clone(), vec![].into_iter())?;
+        serializer.write_text("a b")?;
+        serializer.end_elem(p.clone())?;
+        serializer.write_text("  ")?;
+        serializer.write_text("\n")?;
+        serializer.write_comment("dropped")?;
+        serializer.write_text("\t \n")?;
+        serializer.start_elem(p.clone(), vec![].into_iter())?;
+        serializer.write_text("b")?;
+        serializer.end_elem(p)?;
+        serializer.write_text("\n")?;
+        serializer.start_elem(pre.clone(), vec![].into_iter())?;
+        serializer.write_text("\n  ")?;
+        serializer.end_elem(pre)
+    }
+}
+
+#[test]
+fn whitespace_text() {
+    let opts = |whitespace_text| SerializeOpts {
+        whitespace_text: whitespace_text,
+        preserved_comment_exact: Some(BTreeSet::new()),
+        ..Default::default()
+    };
+    assert_eq!(
+        serialize_to_string(&SpacedParagraphs, opts(WhitespaceText::Preserve)),
+        "<p>a b</p>  \n\t \n<p>b</p>\n<pre>\n  </pre>"
+    );
+    assert_eq!(
+        serialize_to_string(&SpacedParagraphs, opts(WhitespaceText::Remove)),
+        "<p>a b</p><p>b</p><pre>\n  </pre>"
+    );
+    assert_eq!(
+        serialize_to_string(&SpacedParagraphs, opts(WhitespaceText::SingleSpace)),
+        "<p>a b</p> <p>b</p> <pre>\n  </pre>"
+    );
+}
+
+#[test]
+fn whitespace_text_keeps_wbr() {
+    let input = "<p>a <i>long</i> \n <wbr> \t <i>word</i>\u{a0}<br> \n </p>";
+    let opts = |whitespace_text, pretty_print| SerializeOpts {
+        whitespace_text: whitespace_text,
+        pretty_print: pretty_print,
+        inline_elements: [local_name!("i")].iter().cloned().collect(),
+        escape_nbsp: false,
+        ..SerializeOpts::spec_compliant()
+    };
+    let serialize = |whitespace_text, pretty_print| {
+        parse_and_serialize_with(input.to_tendril(), opts(whitespace_text, pretty_print))
+    };
+    assert_eq!(
+        &*serialize(WhitespaceText::SingleSpace, false),
+        "<p>a <i>long</i> <wbr> <i>word</i>\u{a0}<br> </p>"
+    );
+    assert_eq!(
+        &*serialize(WhitespaceText::Remove, false),
+        "<p>a <i>long</i><wbr><i>word</i>\u{a0}<br></p>"
+    );
+    // Left out of `inline_elements`, `<wbr>` and `<br>` still aren't put on
+    // lines of their own.
+    assert_eq!(
+        &*serialize(WhitespaceText::Remove, true),
+        "<p>a <i>long</i><wbr><i>word</i>\u{a0}<br></p>"
+    );
+}
+
+#[test]
+fn literal_nbsp() {
+    let input = "<p title=\"a\u{a0}&amp;\u{a0}&quot;b\">1\u{a0}&amp;\u{a0}&lt;2&gt;</p>";
+    let opts = SerializeOpts {
+        escape_nbsp: false,
+        ..Default::default()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts.clone()),
+        "<p title=\"a\u{a0}&amp;\u{a0}&quot;b\">1\u{a0}&amp;\u{a0}&lt;2&gt;</p>"
+    );
+    let opts = SerializeOpts {
+        ascii_only: true,
+        ..opts
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        "<p title=\"a&#xa0;&amp;&#xa0;&quot;b\">1&#xa0;&amp;&#xa0;&lt;2&gt;</p>"
+    );
+}
+
+#[test]
+fn escaping_writer() {
+    use std::io::Write;
+
+    let mut w = EscapingWriter::new(vec![], false);
+    let input = "a<\u{e9}\u{a0}&\"b\"".as_bytes();
+    // Split the input inside both multi-byte characters.
+    w.write_all(&input[..3]).unwrap();
+    w.write_all(&input[3..5]).unwrap();
+    w.write_all(&input[5..]).unwrap();
+    assert_eq!(
+        String::from_utf8(w.finish().unwrap()).unwrap(),
+        "a&lt;\u{e9}&nbsp;&amp;\"b\""
+    );
+
+    let mut w = EscapingWriter::new(vec![], true);
+    w.write_all("<\"&\u{a0}\"".as_bytes()).unwrap();
+    assert_eq!(w.get_ref(), "<&quot;&amp;&nbsp;&quot;".as_bytes());
+
+    let mut w = EscapingWriter::new(vec![], false);
+    w.write_all(&"\u{e9}".as_bytes()[..1]).unwrap();
+    assert_eq!(w.finish().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    let mut w = EscapingWriter::new(vec![], false);
+    assert!(w.write_all(b"a\xffb").is_err());
+}
+
+#[test]
+fn mirror_lang_to_xml() {
+    let opts = SerializeOpts {
+        mirror_lang_to_xml: true,
+        ..SerializeOpts::polyglot()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<p lang="en-GB">a</p><p>b</p><svg xml:lang="fr" lang="fr"></svg>"#.to_tendril(),
+            SerializeOpts {
+                allowed_tags: None,
+                ..opts
+            }
+        ),
+        r#"<p lang="en-GB" xml:lang="en-GB">a</p><p>b</p><svg xmlns="http://www.w3.org/2000/svg" xml:lang="fr" lang="fr"></svg>"#
+    );
+}
+
+#[test]
+fn pretty_print() {
+    let opts = SerializeOpts {
+        pretty_print: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<div><p>a <b>b</b> <i>c</i></p><!-- note --><p>d</p></div><pre> x\n</pre>"
+                .to_tendril(),
+            opts.clone()
+        ),
+        "<div>\n  <p>a <b>b</b> <i>c</i></p>\n  <!-- note -->\n  <p>d</p>\n</div>\n<pre> x\n</pre>"
+    );
+    // Whitespace around comments is replaced by the indentation.
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<p>a</p>\n   <!--x-->  \n<p>b</p>text<hr>text".to_tendril(),
+            opts
+        ),
+        "<p>a</p>\n<!--x-->\n<p>b</p>\ntext\n<hr>\ntext"
+    );
+}
+
+#[test]
+fn pretty_print_max_line_length() {
+    let input = "<div><form action=/submit/form method=post class=signup-form \
+                 id=signup data-tracking=signup-page>x</form><p id=a>y</p></div>";
+    let opts = SerializeOpts {
+        pretty_print: true,
+        max_line_length: Some(40),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        "<div>\n  <form action=\"/submit/form\"\n        method=\"post\"\n        \
+         class=\"signup-form\"\n        id=\"signup\"\n        \
+         data-tracking=\"signup-page\">x</form>\n  <p id=\"a\">y</p>\n</div>"
+    );
+}
+
+#[test]
+fn pretty_print_no_indent_children() {
+    let input = "<div><p>Hello <user-name>x</user-name> and <del>y</del></p></div>";
+    let opts = SerializeOpts {
+        pretty_print: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    // Custom elements aren't known to be inline.
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts.clone()),
+        "<div>\n  <p>Hello \n    <user-name>x</user-name>\n     and <del>y</del>\n  </p>\n</div>"
+    );
+    let opts = SerializeOpts {
+        no_indent_children: [local_name!("p")].iter().cloned().collect(),
+        ..opts
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        "<div>\n  <p>Hello <user-name>x</user-name> and <del>y</del></p>\n</div>"
+    );
+}
+
+#[test]
+fn pretty_print_indent_with() {
+    let input = "<ul><li><p>a</p></li></ul>";
+    let opts = SerializeOpts {
+        pretty_print: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts.clone()),
+        "<ul>\n  <li>\n    <p>a</p>\n  </li>\n</ul>"
+    );
+    let opts = SerializeOpts {
+        indent_with: "\t".to_string(),
+        ..opts
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        "<ul>\n\t<li>\n\t\t<p>a</p>\n\t</li>\n</ul>"
+    );
+}
+
+#[test]
+fn utf16_writer() {
+    use std::io::Write;
+
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<p>\u{e9}\u{1d11e}</p>");
+    let inner = &dom.document.children.borrow()[0];
+    let expected_be: &[u8] = &[
+        0xFE, 0xFF, 0x00, b'<', 0x00, b'p', 0x00, b'>', 0x00, 0xE9, 0xD8, 0x34, 0xDD, 0x1E, 0x00,
+        b'<', 0x00, b'/', 0x00, b'p', 0x00, b'>',
+    ];
+    let mut w = Utf16Writer::new(vec![], true);
+    serialize(&mut w, inner, SerializeOpts::spec_compliant()).unwrap();
+    assert_eq!(w.finish().unwrap(), expected_be);
+
+    // Split inside the four-byte character.
+    let input = "\u{1d11e}a".as_bytes();
+    let mut w = Utf16Writer::new(vec![], false);
+    w.write_all(&input[..1]).unwrap();
+    w.write_all(&input[1..3]).unwrap();
+    w.write_all(&input[3..]).unwrap();
+    assert_eq!(
+        w.finish().unwrap(),
+        [0xFF, 0xFE, 0x34, 0xD8, 0x1E, 0xDD, b'a', 0x00]
+    );
+
+    let w = Utf16Writer::new(vec![], false);
+    assert_eq!(w.finish().unwrap(), [0xFF, 0xFE]);
+    let mut w = Utf16Writer::new(vec![], false);
+    w.write_all(&"\u{e9}".as_bytes()[..1]).unwrap();
+    assert_eq!(w.finish().unwrap_err().kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn tee_writer() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one(r#"<p title="a&quot;b">1 &lt; 2<br>&nbsp;</p>"#);
+    let mut tee = TeeWriter::new(vec![], vec![]);
+    serialize(&mut tee, &dom.document, SerializeOpts::spec_compliant()).unwrap();
+    let (first, second) = tee.into_inner();
+    assert_eq!(first, second);
+    assert_eq!(
+        String::from_utf8(first).unwrap(),
+        r#"<html><head></head><body><p title="a&quot;b">1 &lt; 2<br>&nbsp;</p></body></html>"#
+    );
+}
+
+#[test]
+fn disallowed_replacement() {
+    let opts = |replacement: &str| SerializeOpts {
+        disallowed_replacement: Some(replacement.to_string()),
+        ..Default::default()
+    };
+    let input = "<p>a<script>alert(1)</script><span><b>b</b></span></p>";
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts("[removed]")),
+        "<p>a[removed][removed]</p>"
+    );
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts("<!--removed-->")),
+        "<p>a<!--removed--><!--removed--></p>"
+    );
+}
+
+/// The names, namespaces, attributes and text of `node` and its descendants.
+fn dump_tree(node: &Handle, out: &mut String) {
+    match node.data {
+        NodeData::Element {
+            ref name,
+            ref attrs,
+            ..
+        } => {
+            out.push_str(&format!("<{{{}}}{}", name.ns, name.local));
+            for attr in attrs.borrow().iter() {
+                out.push_str(&format!(
+                    " {{{}}}{}={:?}",
+                    attr.name.ns, attr.name.local, &*attr.value
+                ));
+            }
+            out.push('>');
+        },
+        NodeData::Text { ref contents } => out.push_str(&contents.borrow()),
+        _ => (),
+    }
+    for child in node.children.borrow().iter() {
+        dump_tree(child, out);
+    }
+    out.push('/');
+}
+
+#[test]
+fn lossless_namespaces() {
+    let parse_xml = |input: &str| {
+        let dom = xml5ever::driver::parse_document(RcDom::default(), Default::default()).one(input);
+        let mut tree = String::new();
+        dump_tree(&dom.document, &mut tree);
+        (dom, tree)
+    };
+    let (dom, tree) = parse_xml(
+        r#"<c:widget xmlns:c="urn:example:custom" c:size="3"><c:part xmlns:x="urn:example:x" x:mode="a">hi</c:part><b:part xmlns:b="urn:example:b"/></c:widget>"#,
+    );
+    let opts = SerializeOpts {
+        lossless_namespaces: true,
+        traversal_scope: TraversalScope::IncludeNode,
+        ..SerializeOpts::spec_compliant()
+    };
+    let widget = dom.document.children.borrow()[0].clone();
+    let mut output = vec![];
+    serialize(&mut output, &widget, opts.clone()).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(parse_xml(&output).1, tree);
+
+    // Without prefixes or declarations of their own, nodes get generated ones.
+    struct Unprefixed;
+    impl Serialize for Unprefixed {
+        fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+        where
+            S: Serializer,
+        {
+            let widget = QualName::new(None, "urn:example:custom".into(), "widget".into());
+            let size = QualName::new(None, "urn:example:other".into(), "size".into());
+            let part = QualName::new(None, "urn:example:custom".into(), "part".into());
+            serializer.start_elem(widget.clone(), vec![(&size, "3")].into_iter())?;
+            serializer.start_elem(part.clone(), vec![].into_iter())?;
+            serializer.end_elem(part)?;
+            serializer.end_elem(widget)
+        }
+    }
+    assert_eq!(
+        serialize_to_string(&Unprefixed, opts),
+        r#"<ns1:widget ns2:size="3" xmlns:ns1="urn:example:custom" xmlns:ns2="urn:example:other"><ns1:part></ns1:part></ns1:widget>"#
+    );
+}
+
+#[test]
+fn lowercase_value_attrs() {
+    let opts = SerializeOpts {
+        lowercase_value_attrs: [local_name!("type"), local_name!("method")]
+            .iter()
+            .cloned()
+            .collect(),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<form METHOD="POST"><input type="TEXT" id="Name" value="ÉA"></form>"#.to_tendril(),
+            opts
+        ),
+        r#"<form method="post"><input type="text" id="Name" value="ÉA"></form>"#
+    );
+}
+
+#[test]
+fn boundary_hashes() {
+    let hashes = |input: &str| {
+        let dom = parse_document(RcDom::default(), ParseOpts::default()).one(input);
+        let mut hashes = vec![];
+        let mut output = vec![];
+        serialize_with_boundaries(
+            &mut output,
+            &dom.document,
+            SerializeOpts::spec_compliant(),
+            |hash| hashes.push(hash),
+        )
+        .unwrap();
+        hashes
+    };
+    let a = hashes("<p>one</p><p>two</p><p>three</p>");
+    // html, head, /head, body, three p's, /body, /html
+    assert_eq!(a.len(), 12);
+    assert_eq!(a, hashes("<p>one</p><p>two</p><p>three</p>"));
+
+    let b = hashes("<p>one</p><p>TWO</p><p>three</p>");
+    assert_eq!(a.len(), b.len());
+    let differing: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+    // Only the segment holding the changed text.
+    assert_eq!(differing, vec![7]);
+}
+
+struct RawDoctype(&'static str);
+
+impl Serialize for RawDoctype {
+    fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        serializer.write_doctype_raw(self.0)
+    }
+}
+
+#[test]
+fn raw_doctype() {
+    let quirky = "HTML  PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\"\n\t\"loose.dtd\"";
+    assert_eq!(
+        serialize_to_string(&RawDoctype(quirky), Default::default()),
+        format!("<!DOCTYPE {}>", quirky)
+    );
+
+    let mut output = vec![];
+    let err = serialize(
+        &mut output,
+        &RawDoctype("html><script>"),
+        Default::default(),
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert!(output.is_empty());
+}
+
+#[test]
+fn allowed_is_values() {
+    let opts = SerializeOpts {
+        allowed_tags: Some(
+            [local_name!("p"), local_name!("button")]
+                .iter()
+                .cloned()
+                .collect(),
+        ),
+        allowed_is_values: Some(["fancy-button".to_string()].iter().cloned().collect()),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<button is="fancy-button">a</button><p>b</p>"#.to_tendril(),
+            opts.clone()
+        ),
+        r#"<button is="fancy-button">a</button><p>b</p>"#
+    );
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<button is="evil-button">a</button><p is="x">b</p>"#.to_tendril(),
+            opts.clone()
+        ),
+        "&lt;button is=&quot;evil-button&quot;&gt;a&lt;/button&gt;&lt;p is=&quot;x&quot;&gt;b&lt;/p&gt;"
+    );
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<p>a<button is="evil-button">b</button></p>"#.to_tendril(),
+            SerializeOpts {
+                disallowed_replacement: Some("[removed]".to_string()),
+                ..opts
+            }
+        ),
+        "<p>a[removed]</p>"
+    );
+}
+
+struct TextWithEntities(&'static str, Vec<EntitySpan<'static>>);
+
+impl Serialize for TextWithEntities {
+    fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        let p = QualName::new(None, ns!(html), local_name!("p"));
+        serializer.start_elem(p.clone(), vec![].into_iter())?;
+        serializer.write_text_with_entities(self.0, &self.1)?;
+        serializer.end_elem(p)
+    }
+}
+
+#[test]
+fn entity_spellings() {
+    let span = |range, source| EntitySpan {
+        range: range,
+        source: source,
+    };
+    // Parsing doesn't record references, so they are only kept when the tree
+    // provides their spans itself.
+    assert_eq!(
+        &*parse_and_serialize("<p>&copy; 2019</p>".to_tendril()),
+        "<p>\u{a9} 2019</p>"
+    );
+
+    // The text of `<p>&copy; 2019 &#x26; &COPY &#169;</p>`, with the references
+    // that were terminated, as a tree built by hand would give it.
+    let node = TextWithEntities(
+        "\u{a9} 2019 & \u{a9} \u{a9}",
+        vec![
+            span(0..2, "&copy;"),
+            span(8..9, "&#x26;"),
+            span(13..15, "&#169;"),
+        ],
+    );
+    let output = serialize_to_string(&node, SerializeOpts::spec_compliant());
+    assert_eq!(output, "<p>&copy; 2019 &#x26; \u{a9} &#169;</p>");
+    assert_eq!(
+        &*parse_and_serialize(output.to_tendril()),
+        "<p>\u{a9} 2019 &amp; \u{a9} \u{a9}</p>"
+    );
+
+    // Spans that don't spell their text are ignored.
+    let node = TextWithEntities(
+        "<\u{a9}\u{a9}",
+        vec![
+            span(0..1, "<script>"),
+            span(1..3, "&amp;"),
+            span(3..5, "&copy"),
+        ],
+    );
+    assert_eq!(
+        serialize_to_string(&node, SerializeOpts::spec_compliant()),
+        "<p>&lt;\u{a9}\u{a9}</p>"
+    );
+}
+
+#[test]
+fn numeric_reference_ranges() {
+    let opts = SerializeOpts {
+        numeric_reference_ranges: vec![0x1F300..=0x1FAFF, 0x2600..=0x26FF],
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<p title=\"\u{1F600}\">\u{1F600} \u{2602} caf\u{e9} \u{2190}</p>".to_tendril(),
+            opts
+        ),
+        "<p title=\"&#x1f600;\">&#x1f600; &#x2602; caf\u{e9} \u{2190}</p>"
+    );
+}
+
+#[test]
+fn serialize_to_string_sized() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<title>t</title><p>caf\u{e9} &amp; <b>more</b></p>");
+    let opts = SerializeOpts::spec_compliant();
+    let expected = serialize_to_string(&dom.document, opts.clone());
+
+    let exact = serialize_to_string_exact(&dom.document, opts.clone()).unwrap();
+    assert_eq!(exact, expected);
+    assert_eq!(exact.capacity(), exact.len());
+
+    let output = serialize_to_string_with_capacity(&dom.document, opts, 1000).unwrap();
+    assert_eq!(output, expected);
+    assert_eq!(output.capacity(), 1000);
+}
+
+#[test]
+fn lazy_load_images() {
+    let opts = SerializeOpts {
+        lazy_load_images: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<img src="a.png"><img src="b.png" loading="eager"><iframe src="c.html"></iframe><video src="d.mp4"></video>"#.to_tendril(),
+            opts
+        ),
+        r#"<img src="a.png" loading="lazy"><img src="b.png" loading="eager"><iframe src="c.html" loading="lazy"></iframe><video src="d.mp4"></video>"#
+    );
+}
+
+#[test]
+fn declarative_shadow_dom() {
+    let input = r#"<div><template shadowrootmode="open"><slot name="a"></slot><p>shadow</p></template><span slot="a">light</span></div>"#;
+    let output = parse_and_serialize(input.to_tendril());
+    assert_eq!(&*output, input);
+    assert_eq!(&*parse_and_serialize(output), input);
+
+    let opts = SerializeOpts {
+        allowed_attributes: Some(BTreeMap::new()),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<template shadowrootmode="closed" class="x"><b>a</b></template>"#.to_tendril(),
+            opts
+        ),
+        r#"<template shadowrootmode="closed"><b>a</b></template>"#
+    );
+}
+
+#[test]
+fn declarative_shadow_dom_options() {
+    let input = r#"<template shadowrootmode="open" shadowrootdelegatesfocus="" shadowrootclonable="" shadowrootserializable="" id="x"><input></template>"#;
+    let opts = SerializeOpts {
+        allowed_attributes: Some(BTreeMap::new()),
+        always_allowed_attributes: BTreeSet::new(),
+        ..SerializeOpts::spec_compliant()
+    };
+    let output = parse_and_serialize_with(input.to_tendril(), opts.clone());
+    assert_eq!(
+        &*output,
+        r#"<template shadowrootmode="open" shadowrootdelegatesfocus="" shadowrootclonable="" shadowrootserializable=""><input></template>"#
+    );
+    assert_eq!(parse_and_serialize_with(output.clone(), opts), output);
+}
+
+#[test]
+fn hex_uppercase() {
+    let input = "<p title=\"\u{e9}\">\u{1F600}\u{a0}</p>";
+    let opts = SerializeOpts {
+        ascii_only: true,
+        output_syntax: OutputSyntax::Xhtml,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts.clone()),
+        "<p title=\"&#xe9;\">&#x1f600;&#xa0;</p>"
+    );
+    let opts = SerializeOpts {
+        hex_uppercase: true,
+        ..opts
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        "<p title=\"&#xE9;\">&#x1F600;&#xA0;</p>"
+    );
+}
+
+#[test]
+fn max_attribute_value_len() {
+    let input = "<p title=\"caf\u{e9} au lait\" id=\"x\">a</p>";
+    let opts = SerializeOpts {
+        max_attribute_value_len: Some(4),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts.clone()),
+        "<p title=\"caf\" id=\"x\">a</p>"
+    );
+    let ellipsis = SerializeOpts {
+        long_attribute_values: LongAttributeValue::TruncateWithEllipsis,
+        ..opts.clone()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), ellipsis),
+        "<p title=\"caf\u{2026}\" id=\"x\">a</p>"
+    );
+    let drop = SerializeOpts {
+        long_attribute_values: LongAttributeValue::Drop,
+        ..opts
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), drop),
+        "<p id=\"x\">a</p>"
+    );
+}
+
+fn markdown(input: &str) -> String {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one(input);
+    let inner = &dom.document.children.borrow()[0];
+    serialize_markdown(inner)
+}
+
+#[test]
+fn markdown_headings() {
+    assert_eq!(
+        markdown("<h1>Title</h1><p>Intro  text.</p><h3> Sub </h3>"),
+        "# Title\n\nIntro text.\n\n### Sub"
+    );
+}
+
+#[test]
+fn markdown_lists() {
+    assert_eq!(
+        markdown("<ul><li>one</li><li>two<ul><li>nested</li></ul></li><li>three</li></ul>"),
+        "- one\n- two\n  - nested\n- three"
+    );
+    assert_eq!(
+        markdown("<p>Steps:</p><ol start=\"3\"><li>a</li><li><p>b</p></li></ol>"),
+        "Steps:\n\n3. a\n4. b"
+    );
+}
+
+#[test]
+fn markdown_links() {
+    assert_eq!(
+        markdown(
+            "See <a href=\"https://example.com/a b\">the <em>docs</em></a> or <a>nothing</a>."
+        ),
+        "See [the *docs*](https://example.com/a%20b) or nothing."
+    );
+}
+
+#[test]
+fn markdown_emphasis() {
+    assert_eq!(
+        markdown("<p><strong>bold</strong>, <b>also</b>, <em>em</em> and <i>i</i> *not*</p>"),
+        "**bold**, **also**, *em* and *i* \\*not\\*"
+    );
+}
+
+#[test]
+fn markdown_code() {
+    assert_eq!(
+        markdown("<p>Run <code>cargo *test*</code>:</p><pre><code>fn main() {\n    go();\n}</code></pre>"),
+        "Run `cargo *test*`:\n\n```\nfn main() {\n    go();\n}\n```"
+    );
+}
+
+#[test]
+fn markdown_code_with_backticks() {
+    assert_eq!(
+        markdown("<p><code>a `b` c</code> and <code>``x</code> and <code></code></p>"),
+        "``a `b` c`` and ``` ``x ``` and"
+    );
+    assert_eq!(
+        markdown("<p><code><b>bold</b> <a href=\"/x\">link</a></code></p>"),
+        "`bold link`"
+    );
+}
+
+#[test]
+fn markdown_pre_with_backticks() {
+    assert_eq!(
+        markdown("<pre>a\n```\n<b>b</b></pre><p>c</p>"),
+        "````\na\n```\nb\n````\n\nc"
+    );
+    assert_eq!(markdown("<pre>~~~\n`x`</pre>"), "```\n~~~\n`x`\n```");
+}
+
+#[test]
+fn markdown_link_destinations() {
+    assert_eq!(
+        markdown(
+            "<a href=\"/a\nb\">x</a> <a href=\"/a\tb<c>\">y</a> <a href=\"javascript:alert(1)\">z</a> \
+             <a href=\" Data:text/html,x\">w</a> <a href=\"mailto:a@example.com\">v</a>"
+        ),
+        "[x](/a%0Ab) [y](/a%09b%3Cc%3E) z w [v](mailto:a@example.com)"
+    );
+}
+
+#[test]
+fn markdown_escapes_markup() {
+    assert_eq!(
+        markdown("<p>&lt;img src=x onerror=alert(1)&gt; &amp;amp; 1 &lt; 2</p>"),
+        "\\<img src=x onerror=alert(1)\\> \\&amp; 1 \\< 2"
+    );
+    assert_eq!(
+        markdown("<p># 1</p><p>- a</p><p>+ b</p><p>2. c</p><p>3) d 4. e</p><p>x<br>===</p>"),
+        "\\# 1\n\n\\- a\n\n\\+ b\n\n2\\. c\n\n3\\) d 4. e\n\nx\\\n\\==="
+    );
+    assert_eq!(
+        markdown("<ul><li># not a heading</li></ul>"),
+        "- \\# not a heading"
+    );
+}
+
+#[test]
+fn markdown_blockquote() {
+    assert_eq!(
+        markdown(
+            "<p>He said:</p><blockquote><p>One.</p><p>Two<br>lines.</p></blockquote><p>After</p>"
+        ),
+        "He said:\n\n> One.\n>\n> Two\\\n> lines.\n\nAfter"
+    );
+}
+
+#[test]
+fn markdown_other_elements() {
+    assert_eq!(
+        markdown(
+            "<div><span>kept</span><script>dropped()</script></div><!--no--><section>too</section>"
+        ),
+        "kept\n\ntoo"
+    );
+}
+
+#[test]
+fn wrapped() {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<p>a</p>b");
+    let inner = &dom.document.children.borrow()[0];
+    let class = QualName::new(None, ns!(), local_name!("class"));
+    let mut result = vec![];
+    serialize_wrapped(
+        &mut result,
+        inner,
+        QualName::new(None, ns!(html), local_name!("div")),
+        &[(&class, "x")],
+        SerializeOpts::spec_compliant(),
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(result).unwrap(),
+        r#"<div class="x"><p>a</p>b</div>"#
+    );
+    assert_eq!(inner.children.borrow().len(), 2);
+}
+
+/// A writer that sets a flag once more than `limit` bytes have been written.
+struct CancelAfter<'a> {
+    output: Vec<u8>,
+    limit: usize,
+    flag: &'a AtomicBool,
+}
+
+impl<'a> io::Write for CancelAfter<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.extend_from_slice(buf);
+        if self.output.len() > self.limit {
+            self.flag.store(true, Ordering::Relaxed);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn cancellable() {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<p>1</p><p>2</p><p>3</p>");
+    let inner = &dom.document.children.borrow()[0];
+    let opts = SerializeOpts::spec_compliant();
+
+    let flag = AtomicBool::new(false);
+    let mut writer = CancelAfter {
+        output: vec![],
+        limit: 4,
+        flag: &flag,
+    };
+    let err = serialize_cancellable(&mut writer, inner, opts.clone(), &flag).unwrap_err();
+    assert_eq!(
+        err.get_ref().unwrap().downcast_ref::<Cancelled>(),
+        Some(&Cancelled)
+    );
+    assert_eq!(String::from_utf8(writer.output).unwrap(), "<p>1</p>");
+
+    let flag = AtomicBool::new(false);
+    let mut output = vec![];
+    serialize_cancellable(&mut output, inner, opts, &flag).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<p>1</p><p>2</p><p>3</p>"
+    );
+}
+
+#[test]
+fn after_plaintext() {
+    // The tokenizer alone doesn't switch to the PLAINTEXT state, so markup
+    // follows the `<plaintext>` here, as it can't in a parsed tree.
+    let output = tokenize_and_serialize(
+        "<div><plaintext>a &amp; b</plaintext></div><p>c &lt; <i>d</i></p>".to_tendril(),
+    );
+    assert_eq!(&*output, "<div><plaintext>a & b<p>c < <i>d</i></p>");
+    assert_eq!(parse_and_serialize(output.clone()), output);
+}
+
+#[test]
+fn pretty_print_inline_elements() {
+    let input = "<p>Status: <x-badge>new</x-badge> <span>!</span></p>";
+    let mut opts = SerializeOpts {
+        pretty_print: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts.clone()),
+        "<p>Status: \n  <x-badge>new</x-badge>\n  <span>!</span>\n</p>"
+    );
+    opts.inline_elements.insert(LocalName::from("x-badge"));
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        "<p>Status: <x-badge>new</x-badge> <span>!</span></p>"
+    );
+}
+
+#[test]
+fn src_prefix() {
+    let opts = SerializeOpts {
+        src_prefix: Some("https://cdn.example".to_string()),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r##"<img src="/images/a.png"><img src="https://other/x.png"><img src="b.png"><a href="#top">t</a><a href="//host/c">c</a>"##.to_tendril(),
+            opts
+        ),
+        r##"<img src="https://cdn.example/images/a.png"><img src="https://other/x.png"><img src="https://cdn.example/b.png"><a href="#top">t</a><a href="//host/c">c</a>"##
+    );
+}
+
+#[test]
+fn preserve_self_closing() {
+    let input = "<p>a<br>b<br/>c</p><hr/><hr><svg><circle/></svg>";
+    let opts = SerializeOpts {
+        preserve_self_closing: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        input.replace("<circle/>", "<circle></circle>")
+    );
+    assert_eq!(
+        &*parse_and_serialize(input.to_tendril()),
+        "<p>a<br>b<br>c</p><hr><hr><svg><circle></circle></svg>"
+    );
+}
+
+#[test]
+fn drop_default_attributes() {
+    let input = r#"<form method="GET" action="/a"><input type="text" name="u"><input type="password" name="p"><button type="submit">Go</button></form>"#;
+    let opts = SerializeOpts {
+        drop_default_attributes: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        r#"<form action="/a"><input name="u"><input type="password" name="p"><button>Go</button></form>"#
+    );
+    assert_eq!(&*parse_and_serialize(input.to_tendril()), input);
+}
+
+#[test]
+fn editor_markers() {
+    let input = "<div><p>Hel<!--cursor-->lo<!--note--></p></div>";
+    let opts = SerializeOpts {
+        allowed_tags: None,
+        preserved_comment_exact: Some(BTreeSet::new()),
+        editor_markers: vec!["cursor".to_string()].into_iter().collect(),
+        ..Default::default()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts.clone()),
+        "<div><p>Hel<!--cursor-->lo</p></div>"
+    );
+    let opts = SerializeOpts {
+        pretty_print: true,
+        ..opts
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        "<div>\n  <p>Hel<!--cursor-->lo</p>\n</div>"
+    );
+}
+
+#[test]
+#[cfg(feature = "unicode-normalization")]
+fn normalize_nfc() {
+    let input = "<p title=\"cafe\u{301}\">cafe\u{301}</p><script>\"e\u{301}\"</script>";
+    let opts = SerializeOpts {
+        normalize_nfc: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(input.to_tendril(), opts),
+        "<p title=\"caf\u{e9}\">caf\u{e9}</p><script>\"e\u{301}\"</script>"
+    );
+    assert_eq!(&*parse_and_serialize(input.to_tendril()), input);
+}
+
+#[test]
+fn sanitize_html_payloads() {
+    let sanitize = |input| sanitize_html(input, SerializeOpts::ugc()).unwrap();
+    assert_eq!(
+        sanitize("<p>Hi <script>alert(1)</script></p>"),
+        "<p>Hi &lt;script&gt;alert(1)&lt;/script&gt;</p>"
+    );
+    assert_eq!(
+        sanitize(r#"<img src="x.png" onerror="alert(1)">"#),
+        r#"<img src="x.png">"#
+    );
+    assert_eq!(
+        sanitize(r#"<a href=" jAvAsCrIpT:alert(1)" onclick="alert(2)">x</a>"#),
+        r#"<a rel="nofollow noopener">x</a>"#
+    );
+    assert_eq!(
+        sanitize(r#"<svg><script>alert(1)</script></svg><iframe src="//evil"></iframe>"#),
+        "&lt;svg&gt;&lt;script&gt;alert(1)&lt;/script&gt;&lt;/svg&gt;&lt;iframe&gt;&lt;/iframe&gt;"
+    );
+    assert_eq!(
+        sanitize("<p title='\"><script>alert(1)</script>'>x</p><!--><script>-->"),
+        "<p title=\"&quot;><script>alert(1)</script>\">x</p><!---->&lt;script&gt;--&gt;&lt;/script&gt;"
+    );
+}
+
+#[test]
+fn style_sanitizer() {
+    let opts = SerializeOpts {
+        style_sanitizer: Some(StyleSanitizer::allow_properties(vec![
+            "color",
+            "background-color",
+            "width",
+        ])),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<p style="COLOR: red; width: expression(alert(1)); background-color: rgb(0, 0, 0)">a</p><p style="background-color: url(javascript:alert(1)); position: fixed">b</p><p style="width: calc(100% - 1px)">c</p>"#.to_tendril(),
+            opts.clone()
+        ),
+        r#"<p style="color: red; background-color: rgb(0, 0, 0)">a</p><p>b</p><p style="width: calc(100% - 1px)">c</p>"#
+    );
+
+    let opts = SerializeOpts {
+        style_sanitizer: Some(StyleSanitizer::new(|style| style.to_uppercase())),
+        ..opts
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(r#"<b style="color: red">x</b>"#.to_tendril(), opts),
+        r#"<b style="COLOR: RED">x</b>"#
+    );
+}
+
+#[test]
+fn duplicate_ids() {
+    let input = r#"<h2 id="a">1</h2><p><a id="a"></a><b id="a-2">2</b></p><h2 id="a">3</h2>"#;
+    let with = |duplicate_ids| {
+        parse_and_serialize_with(
+            input.to_tendril(),
+            SerializeOpts {
+                duplicate_ids: duplicate_ids,
+                ..SerializeOpts::spec_compliant()
+            },
+        )
+    };
+    assert_eq!(&*with(DuplicateIds::Keep), input);
+    assert_eq!(
+        &*with(DuplicateIds::Drop),
+        r#"<h2 id="a">1</h2><p><a></a><b id="a-2">2</b></p><h2>3</h2>"#
+    );
+    assert_eq!(
+        &*with(DuplicateIds::Suffix),
+        r#"<h2 id="a">1</h2><p><a id="a-2"></a><b id="a-2-2">2</b></p><h2 id="a-3">3</h2>"#
+    );
+}
+
+#[test]
+fn summarized() {
+    let summarize = |input: &str, max_depth, max_nodes| {
+        let dom = parse_fragment(
+            RcDom::default(),
+            ParseOpts::default(),
+            QualName::new(None, ns!(html), local_name!("body")),
+            vec![],
+        )
+        .one(input);
+        let mut result = vec![];
+        serialize_summarized(
+            &mut result,
+            &dom.document.children.borrow()[0],
+            SerializeOpts::spec_compliant(),
+            max_depth,
+            max_nodes,
+        )
+        .unwrap();
+        String::from_utf8(result).unwrap()
+    };
+    let items = "<li>x</li>".repeat(20);
+    assert_eq!(
+        summarize(
+            &format!("<div><ul>{}</ul><p>short <b>text</b></p></div>", items),
+            10,
+            10
+        ),
+        "<div><ul>…(40 nodes)…</ul><p>short <b>text</b></p></div>"
+    );
+    assert_eq!(
+        summarize("<div><p><b>x</b></p><p>y</p><br></div>", 2, 100),
+        "<div><p>…(2 nodes)…</p><p>…(1 node)…</p><br></div>"
+    );
+}
+
+#[test]
+fn text_modes() {
+    let html = |local| QualName::new(None, ns!(html), local);
+    assert_eq!(text_mode(&html(local_name!("div"))), TextMode::Normal);
+    assert_eq!(text_mode(&html(local_name!("p"))), TextMode::Normal);
+    assert_eq!(text_mode(&html(local_name!("title"))), TextMode::Rcdata);
+    assert_eq!(text_mode(&html(local_name!("textarea"))), TextMode::Rcdata);
+    assert_eq!(text_mode(&html(local_name!("script"))), TextMode::RawText);
+    assert_eq!(text_mode(&html(local_name!("style"))), TextMode::RawText);
+    assert_eq!(text_mode(&html(local_name!("xmp"))), TextMode::RawText);
+    assert_eq!(text_mode(&html(local_name!("noscript"))), TextMode::RawText);
+    assert_eq!(
+        text_mode(&QualName::new(None, ns!(svg), local_name!("script"))),
+        TextMode::Normal
+    );
+    assert_eq!(
+        text_mode(&QualName::new(None, ns!(svg), local_name!("title"))),
+        TextMode::Normal
+    );
+}
+
+#[test]
+fn sourcemap() {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one(r#"<p class="x">a<b>b &amp; c</b><!--c--></p><br>d"#);
+    let mut html = vec![];
+    let mut map = vec![];
+    serialize_with_sourcemap(
+        &mut html,
+        &mut map,
+        &dom.document.children.borrow()[0],
+        SerializeOpts::spec_compliant(),
+    )
+    .unwrap();
+    let html = String::from_utf8(html).unwrap();
+    let map = String::from_utf8(map).unwrap();
+    assert_eq!(html, r#"<p class="x">a<b>b &amp; c</b><!--c--></p><br>d"#);
+    assert_eq!(
+        map,
+        r#"{"version":1,"mappings":[[0,13,0],[13,14,1],[14,17,2],[17,26,3],[26,30,2],[30,38,4],[38,42,0],[42,46,5],[46,47,6]]}"#
+    );
+
+    // The ranges cover the output, in order and without gaps.
+    let mappings = &map["{\"version\":1,\"mappings\":[[".len()..map.len() - "]]}".len()];
+    let mut covered = 0;
+    for mapping in mappings.split("],[") {
+        let numbers: Vec<usize> = mapping.split(',').map(|n| n.parse().unwrap()).collect();
+        assert_eq!(numbers[0], covered);
+        assert!(numbers[1] > numbers[0]);
+        covered = numbers[1];
+    }
+    assert_eq!(covered, html.len());
+}
+
+#[test]
+fn entity_style() {
+    let input = r#"<p title="&amp;&quot;'<>">&amp;"'&lt;&gt;</p>"#;
+    let with = |entity_style| {
+        parse_and_serialize_with(
+            input.to_tendril(),
+            SerializeOpts {
+                entity_style: entity_style,
+                ..SerializeOpts::spec_compliant()
+            },
+        )
+    };
+    assert_eq!(
+        &*with(EntityStyle::Minimal),
+        r#"<p title="&amp;&quot;'<>">&amp;"'&lt;&gt;</p>"#
+    );
+    assert_eq!(
+        &*with(EntityStyle::Named),
+        r#"<p title="&amp;&quot;&apos;&lt;&gt;">&amp;&quot;&apos;&lt;&gt;</p>"#
+    );
+    assert_eq!(
+        &*with(EntityStyle::Numeric),
+        r#"<p title="&#x26;&#x22;&#x27;&#x3c;&#x3e;">&#x26;&#x22;&#x27;&#x3c;&#x3e;</p>"#
+    );
+}
+
+#[test]
+fn ensure_charset() {
+    let serialize_document = |input: &str, scope| {
+        let dom = parse_document(RcDom::default(), ParseOpts::default()).one(input);
+        let html = dom
+            .document
+            .children
+            .borrow()
+            .iter()
+            .find(|node| match node.data {
+                NodeData::Element { .. } => true,
+                _ => false,
+            })
+            .unwrap()
+            .clone();
+        let opts = SerializeOpts {
+            ensure_charset: true,
+            traversal_scope: scope,
+            ..SerializeOpts::spec_compliant()
+        };
+        let mut result = vec![];
+        serialize(&mut result, &html, opts).unwrap();
+        String::from_utf8(result).unwrap()
+    };
+    assert_eq!(
+        serialize_document(
+            "<title>a</title><link rel=icon href=x><p>b",
+            TraversalScope::IncludeNode
+        ),
+        r#"<html><head><meta charset="utf-8"><title>a</title><link rel="icon" href="x"></head><body><p>b</p></body></html>"#
+    );
+    assert_eq!(
+        serialize_document(
+            r#"<title>a</title><meta charset="windows-1252"><p>b"#,
+            TraversalScope::IncludeNode
+        ),
+        r#"<html><head><title>a</title><meta charset="windows-1252"></head><body><p>b</p></body></html>"#
+    );
+    assert_eq!(
+        serialize_document(
+            r#"<meta http-equiv="Content-Type" content="text/html; charset=utf-8">"#,
+            TraversalScope::IncludeNode
+        ),
+        r#"<html><head><meta http-equiv="Content-Type" content="text/html; charset=utf-8"></head><body></body></html>"#
+    );
+    // Fragments are left alone.
+    assert_eq!(
+        serialize_document("<p>b", TraversalScope::ChildrenOnly(None)),
+        "<head></head><body><p>b</p></body>"
+    );
+}
+
+#[test]
+fn xml_namespaced_attributes() {
+    struct Namespaced;
+    impl Serialize for Namespaced {
+        fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+        where
+            S: Serializer,
+        {
+            let div = QualName::new(None, ns!(html), local_name!("div"));
+            let state = QualName::new(None, "urn:example:data".into(), "state".into());
+            let svg = QualName::new(None, ns!(svg), local_name!("svg"));
+            let a = QualName::new(None, ns!(svg), local_name!("a"));
+            let href = QualName::new(None, ns!(xlink), local_name!("href"));
+            serializer.start_elem(div.clone(), vec![(&state, "open")].into_iter())?;
+            serializer.start_elem(svg.clone(), vec![].into_iter())?;
+            serializer.start_elem(
+                a.clone(),
+                vec![(&href, "#x"), (&state, "closed")].into_iter(),
+            )?;
+            serializer.end_elem(a)?;
+            serializer.end_elem(svg)?;
+            serializer.end_elem(div)
+        }
+    }
+    let serialize_as = |syntax| {
+        let opts = SerializeOpts {
+            output_syntax: syntax,
+            ..SerializeOpts::spec_compliant()
+        };
+        serialize_to_string(&Namespaced, opts)
+    };
+    assert_eq!(
+        serialize_as(OutputSyntax::Xml),
+        r##"<div ns1:state="open" xmlns:ns1="urn:example:data"><svg xmlns="http://www.w3.org/2000/svg"><a xlink:href="#x" ns1:state="closed" xmlns:xlink="http://www.w3.org/1999/xlink"></a></svg></div>"##
+    );
+    // The HTML syntax has no way to declare them.
+    assert_eq!(
+        serialize_as(OutputSyntax::Html),
+        r##"<div unknown_namespace:state="open"><svg><a xlink:href="#x" unknown_namespace:state="closed"></a></svg></div>"##
+    );
+}
+
+#[test]
+fn minimal_amp_escaping() {
+    let opts = SerializeOpts {
+        minimal_amp_escaping: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    let input =
+        "&amp;amp x|&amp;amp|&amp; |&amp;foo;|&amp;foo|&amp;#39|&amp;#x|&amp;copy=2|&amp;notit";
+    assert_eq!(
+        &*parse_and_serialize_with(
+            format!(r#"<a href="{0}">{0}</a>"#, input).to_tendril(),
+            opts
+        ),
+        "<a href=\"&amp;amp x|&amp;amp|& |&amp;foo;|&foo|&amp;#39|&#x|&copy=2|&notit\">\
+         &amp;amp x|&amp;amp|& |&amp;foo;|&foo|&amp;#39|&#x|&amp;copy=2|&amp;notit</a>"
+    );
+}
+
+#[test]
+fn doctype_only_in_no_quirks() {
+    let opts = SerializeOpts {
+        doctype_only_in_no_quirks: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    let standards =
+        parse_document(RcDom::default(), ParseOpts::default()).one("<!DOCTYPE html><p>x");
+    assert_eq!(
+        serialize_to_string(&standards, opts.clone()),
+        "<!DOCTYPE html><html><head></head><body><p>x</p></body></html>"
+    );
+    let quirks = parse_document(RcDom::default(), ParseOpts::default())
+        .one(r#"<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 3.2 Final//EN"><p>x"#);
+    assert_eq!(
+        serialize_to_string(&quirks, opts.clone()),
+        "<html><head></head><body><p>x</p></body></html>"
+    );
+    // The document node alone doesn't know its quirks mode.
+    assert_eq!(
+        serialize_to_string(&quirks.document, opts),
+        "<!DOCTYPE html><html><head></head><body><p>x</p></body></html>"
+    );
+}
+
+#[test]
+fn null_writer() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<!DOCTYPE html><title>caf\u{e9}</title><p class=x>a &amp; b<br>c");
+    let mut counter = NullWriter::new();
+    serialize(&mut counter, &dom.document, SerializeOpts::default()).unwrap();
+    let output = serialize_to_string(&dom.document, SerializeOpts::default());
+    assert_eq!(counter.count(), output.len());
+}
+
+#[test]
+fn as_attribute_value() {
+    let parse = |input: &str| {
+        parse_fragment(
+            RcDom::default(),
+            ParseOpts::default(),
+            QualName::new(None, ns!(html), local_name!("body")),
+            vec![],
+        )
+        .one(input)
+    };
+    let dom = parse(r#"<p title='say "hi"'>a &amp; b&nbsp;&lt;c&gt;</p>"#);
+    let html = &dom.document.children.borrow()[0];
+    let opts = SerializeOpts::spec_compliant();
+    let serialized = serialize_to_string(html, opts.clone());
+    let value = serialize_as_attribute_value(html, opts).unwrap();
+    assert_eq!(
+        value,
+        "<p title=&quot;say &amp;quot;hi&amp;quot;&quot;>a &amp;amp; b&amp;nbsp;&amp;lt;c&amp;gt;</p>"
+    );
+
+    let embedded = parse(&format!(r#"<div data-content="{}"></div>"#, value));
+    let html = &embedded.document.children.borrow()[0];
+    let div = &html.children.borrow()[0];
+    let content = match div.data {
+        NodeData::Element { ref attrs, .. } => attrs.borrow()[0].value.to_string(),
+        _ => panic!("not an element"),
+    };
+    assert_eq!(content, serialized);
+}
+
+#[test]
+fn canonicalize_token_attrs() {
+    let opts = SerializeOpts {
+        canonicalize_token_attrs: SerializeOpts::token_list_attributes(),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<p class=\"b a  a\tc\" title=\"b a\">x</p>\
+             <a rel=\" noopener nofollow noopener \" href=x>y</a><span class=\"  \"></span>"
+                .to_tendril(),
+            opts
+        ),
+        "<p class=\"a b c\" title=\"b a\">x</p>\
+         <a rel=\"nofollow noopener\" href=\"x\">y</a><span class=\"\"></span>"
+    );
+}
+
+#[test]
+fn noscript_round_trip() {
+    fn parse(input: &str, scripting_enabled: bool) -> RcDom {
+        let opts = ParseOpts {
+            tree_builder: TreeBuilderOpts {
+                scripting_enabled: scripting_enabled,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        parse_fragment(
+            RcDom::default(),
+            opts,
+            QualName::new(None, ns!(html), local_name!("body")),
+            vec![],
+        )
+        .one(input)
+    }
+    fn tree(dom: &RcDom) -> String {
+        let mut out = String::new();
+        dump_tree(&dom.document.children.borrow()[0], &mut out);
+        out
+    }
+    fn write(dom: &RcDom, scripting_enabled: bool) -> String {
+        let opts = SerializeOpts {
+            scripting_enabled: scripting_enabled,
+            ..SerializeOpts::spec_compliant()
+        };
+        serialize_to_string(&dom.document.children.borrow()[0], opts)
+    }
+
+    let input = "<noscript><p>x</p></noscript>";
+    for &scripting_enabled in &[true, false] {
+        let parsed = parse(input, scripting_enabled);
+        let output = write(&parsed, scripting_enabled);
+        assert_eq!(output, input);
+        assert_eq!(tree(&parse(&output, scripting_enabled)), tree(&parsed));
+    }
+
+    // With scripting, the `<p>` is text, which a parser without scripting
+    // would read as markup unless it is escaped.
+    let parsed = parse(input, true);
+    assert_eq!(
+        tree(&parsed),
+        "<{http://www.w3.org/1999/xhtml}html><{http://www.w3.org/1999/xhtml}noscript><p>x</p>///"
+    );
+    let output = write(&parsed, false);
+    assert_eq!(output, "<noscript>&lt;p&gt;x&lt;/p&gt;</noscript>");
+    assert_eq!(tree(&parse(&output, false)), tree(&parsed));
+}
+
+#[test]
+fn serialize_noscript_as_elements() {
+    // Parsed with scripting, the contents are a single text node.
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<noscript><p>x &amp; y</p></noscript>");
+    let write = |scripting_enabled: bool, as_elements: Option<bool>| {
+        let opts = SerializeOpts {
+            scripting_enabled: scripting_enabled,
+            serialize_noscript_as_elements: as_elements,
+            ..SerializeOpts::spec_compliant()
+        };
+        serialize_to_string(&dom.document.children.borrow()[0], opts)
+    };
+
+    assert_eq!(write(true, None), "<noscript><p>x &amp; y</p></noscript>");
+    assert_eq!(
+        write(true, Some(true)),
+        "<noscript>&lt;p&gt;x &amp;amp; y&lt;/p&gt;</noscript>"
+    );
+    assert_eq!(
+        write(false, Some(false)),
+        "<noscript><p>x &amp; y</p></noscript>"
+    );
+    assert_eq!(write(false, None), write(true, Some(true)));
+}
+
+#[test]
+fn element_hook() {
+    let opts = SerializeOpts {
+        element_hook: Some(ElementHook::new(|name, attrs| {
+            if name.local == local_name!("p") {
+                attrs.retain(|&(ref name, _)| name.local != local_name!("class"));
+                let style = QualName::new(None, ns!(), local_name!("style"));
+                attrs.push((style, "color: red".to_owned()));
+            }
+        })),
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with("<p class=a id=b>x</p><b class=a>y</b>".to_tendril(), opts),
+        "<p id=\"b\" style=\"color: red\">x</p><b class=\"a\">y</b>"
+    );
+}
+
+#[test]
+fn strip_event_handlers() {
+    let opts = SerializeOpts {
+        strip_event_handlers: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<p onclick=\"a()\" title=on>x</p><img ONERROR=b() src=c.png>".to_tendril(),
+            opts.clone()
+        ),
+        "<p title=\"on\">x</p><img src=\"c.png\">"
+    );
+    let onerror = QualName::new(None, ns!(), LocalName::from("ONERROR"));
+    let font = QualName::new(None, ns!(), LocalName::from("font"));
+    let attrs = vec![(&onerror, "b()"), (&font, "x")];
+    let img = QualName::new(None, ns!(html), local_name!("img"));
+    let mut ser = HtmlSerializer::new(vec![], opts);
+    ser.start_elem(img, attrs.into_iter()).unwrap();
+    assert_eq!(ser.into_inner(), b"<img font=\"x\">");
+}
+
+#[test]
+fn strip_tracking() {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one(
+        "<a href=\"https://example.com/p?id=1&utm_source=news&x=2#top\" ping=/track \
+         referrerpolicy=unsafe-url title=t>a</a><img src=\"b.png?utm_source=x\">",
+    );
+    let inner = &dom.document.children.borrow()[0];
+    let opts = SerializeOpts::spec_compliant();
+
+    let mut result = vec![];
+    serialize_without_tracking(&mut result, inner, opts.clone()).unwrap();
+    assert_eq!(
+        String::from_utf8(result).unwrap(),
+        "<a href=\"https://example.com/p?id=1&amp;x=2#top\" title=\"t\">a</a><img src=\"b.png\">"
+    );
+
+    let params = ["x".to_string()].iter().cloned().collect();
+    let mut ser = StripTrackingSerializer::with_params(HtmlSerializer::new(vec![], opts), params);
+    inner
+        .serialize(&mut ser, TraversalScope::ChildrenOnly(None))
+        .unwrap();
+    assert_eq!(
+        String::from_utf8(ser.into_inner().into_inner()).unwrap(),
+        "<a href=\"https://example.com/p?id=1&amp;utm_source=news#top\" title=\"t\">a</a>\
+         <img src=\"b.png?utm_source=x\">"
+    );
+}
+
+/// A document with text between the doctype and the `<html>`, and between the
+/// `<head>` and the `<body>`, which the parser would never produce.
+struct StrayDocumentText;
+
+impl Serialize for StrayDocumentText {
+    fn serialize<S>(&self, serializer: &mut S, _: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        let html = |local| QualName::new(None, ns!(html), local);
+        serializer.write_doctype("html")?;
+        serializer.write_text("\n")?;
+        serializer.write_text("a")?;
+        serializer.start_elem(html(local_name!("html")), vec![].into_iter())?;
+        serializer.start_elem(html(local_name!("head")), vec![].into_iter())?;
+        serializer.end_elem(html(local_name!("head")))?;
+        serializer.write_text("b")?;
+        serializer.start_elem(html(local_name!("body")), vec![].into_iter())?;
+        serializer.write_text("c")?;
+        serializer.end_elem(html(local_name!("body")))?;
+        serializer.end_elem(html(local_name!("html")))
+    }
+}
+
+#[test]
+fn stray_text() {
+    let opts = |stray_text| SerializeOpts {
+        stray_text: stray_text,
+        ..SerializeOpts::spec_compliant()
+    };
+    let expected = "<!DOCTYPE html>\na<html><head></head>b<body>c</body></html>";
+    assert_eq!(
+        serialize_to_string(&StrayDocumentText, opts(StrayText::Keep)),
+        expected
+    );
+    assert_eq!(
+        serialize_to_string(&StrayDocumentText, opts(StrayText::Warn)),
+        expected
+    );
+    assert_eq!(
+        serialize_to_string(&StrayDocumentText, opts(StrayText::Drop)),
+        "<!DOCTYPE html>\n<html><head></head><body>c</body></html>"
+    );
+
+    let mut result = vec![];
+    let err = serialize(&mut result, &StrayDocumentText, opts(StrayText::Error)).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(result, b"<!DOCTYPE html>\n");
+
+    // Text at the top level of a fragment is fine.
+    assert_eq!(
+        &*parse_and_serialize_with("a<b>b</b>".to_tendril(), opts(StrayText::Error)),
+        "a<b>b</b>"
+    );
+}
+
+#[test]
+fn amp() {
+    assert_eq!(
+        &*parse_and_serialize_with(
+            "<p>a<img src=x.png width=2 height=1 onload=f()><script>alert(1)</script>b</p>\
+             <form><input></form><div on=tap:menu.open>c</div>"
+                .to_tendril(),
+            SerializeOpts::amp()
+        ),
+        "<p>a<amp-img src=\"x.png\" width=\"2\" height=\"1\"></amp-img>b</p><div on=\"tap:menu.open\">c</div>"
+    );
+    let dom = parse_document(RcDom::default(), ParseOpts::default()).one(
+        "<!doctype html><html amp><head>\
+         <script async src=\"https://cdn.ampproject.org/v0.js\"></script>\
+         <script src=\"https://example.com/x.js\"></script>\
+         <script type=\"application/ld+json\">{}</script></head><body></body></html>",
+    );
+    assert_eq!(
+        serialize_to_string(&dom.document, SerializeOpts::amp()),
+        "<!DOCTYPE html><html amp=\"\"><head>\
+         <script async=\"\" src=\"https://cdn.ampproject.org/v0.js\"></script>\
+         <script type=\"application/ld+json\">{}</script></head><body></body></html>"
+    );
+}
+
+#[test]
+fn serialize_tokens_keeps_stray_end_tags() {
+    let input =
+        "<!doctype html public '-//W3C//DTD HTML 4.01//EN'><p class=a&amp;b>x &lt; y</b></p\
+                 ><br/><!-- c --></div>";
+    let mut tokenizer = Tokenizer::new(Tokens(vec![]), Default::default());
+    let mut queue = ::html5ever::tokenizer::BufferQueue::new();
+    queue.push_back(StrTendril::from_slice(input));
+    tokenizer.feed(&mut queue);
+    tokenizer.end();
+
+    let mut output = vec![];
+    serialize_tokens(
+        &mut output,
+        &tokenizer.sink.0,
+        SerializeOpts::spec_compliant(),
+    )
+    .unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\">\
+         <p class=\"a&amp;b\">x &lt; y</b></p><br/><!-- c --></div>"
+    );
+}
+
+fn tokenize(input: &str) -> Vec<Token> {
+    let mut tokenizer = Tokenizer::new(Tokens(vec![]), Default::default());
+    let mut queue = ::html5ever::tokenizer::BufferQueue::new();
+    queue.push_back(StrTendril::from_slice(input));
+    tokenizer.feed(&mut queue);
+    tokenizer.end();
+    tokenizer.sink.0
+}
+
+#[test]
+fn serialize_tokens_escapes_decoded_raw_text() {
+    // A plain tokenizer decodes the references in `<style>`, so writing its
+    // text raw would close the element and start a script.
+    let tokens = tokenize("<style>&lt;/style&gt;&lt;script&gt;alert(1)&lt;/script&gt;</style>");
+    let mut output = vec![];
+    serialize_tokens(&mut output, &tokens, Default::default()).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<style>&lt;/style&gt;&lt;script&gt;alert(1)&lt;/script&gt;</style>"
+    );
+
+    let mut ser = TokenSerializer::with_raw_text(vec![], Default::default());
+    for token in tokenize("<style>a > b</style><p>a > b</p>").iter() {
+        ser.write_token(token).unwrap();
+    }
+    assert_eq!(
+        String::from_utf8(ser.into_inner()).unwrap(),
+        "<style>a > b</style><p>a &gt; b</p>"
+    );
+}
+
+#[test]
+fn serialize_tokens_unwritable_names() {
+    let tokens = tokenize("<p \"a=1 b=2 c<=3>x</p><a\"b>y</a\"b>");
+    let mut output = vec![];
+    serialize_tokens(&mut output, &tokens, Default::default()).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<p b=\"2\">x</p>&lt;a\"b&gt;y&lt;/a\"b&gt;"
+    );
+}
+
+#[test]
+fn text_index() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<title>Notes</title><p>a <b>b</b> c</p>\n<ul><li>d<svg><desc>e</desc></svg></ul>");
+    let records = serialize_text_index(&dom.document);
+    let records: Vec<(&str, &str)> = records
+        .iter()
+        .map(|&(ref path, ref text)| (&**path, &**text))
+        .collect();
+    assert_eq!(
+        records,
+        vec![
+            ("html>head>title", "Notes"),
+            ("html>body>p", "a "),
+            ("html>body>p>b", "b"),
+            ("html>body>p", " c"),
+            ("html>body>ul>li", "d"),
+            ("html>body>ul>li>svg>desc", "e"),
+        ]
+    );
+
+    let mut ser = TextIndexSerializer::new();
+    dom.document
+        .serialize(&mut ser, TraversalScope::ChildrenOnly(None))
+        .unwrap();
+    assert_eq!(ser.into_records().len(), 6);
+}
+
+#[test]
+fn omit_html_body_tags() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<!DOCTYPE html><title>t</title><body class=x><div><p>a</p></div>");
+    let opts = SerializeOpts {
+        omit_html_body_tags: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        serialize_to_string(&dom.document, opts.clone()),
+        "<!DOCTYPE html><title>t</title><div><p>a</p></div>"
+    );
+
+    // The contents are indented as if the tags left out weren't there.
+    let opts = SerializeOpts {
+        pretty_print: true,
+        ..opts
+    };
+    assert_eq!(
+        serialize_to_string(&dom.document, opts),
+        "<!DOCTYPE html>\n<title>t</title>\n<div>\n  <p>a</p>\n</div>"
+    );
+}
+
+#[test]
+fn escape_json_attribute_value() {
+    let json = r#"{"q":"fish & chips","tags":["a<b"]}"#;
+    let value = escape_attribute_value(json);
+    assert_eq!(
+        value,
+        "{&quot;q&quot;:&quot;fish &amp; chips&quot;,&quot;tags&quot;:[&quot;a<b&quot;]}"
+    );
+
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one(format!(r#"<div data-config="{}"></div>"#, value));
+    let html = &dom.document.children.borrow()[0];
+    let div = &html.children.borrow()[0];
+    match div.data {
+        NodeData::Element { ref attrs, .. } => assert_eq!(&*attrs.borrow()[0].value, json),
+        _ => panic!("not an element"),
+    }
+}
+
+#[test]
+fn deterministic_output() {
+    let input = r#"<p id=a class="z y x" title=t data-b=1 data-a=2>text</p><svg xmlns:xlink="http://www.w3.org/1999/xlink"><use xlink:href="/icons.svg" xml:lang=en /></svg>"#;
+    let opts = |reversed: bool| {
+        let mut attrs = vec!["title", "id", "class", "data-a", "data-b", "href"];
+        if reversed {
+            attrs.reverse();
+        }
+        let allowed: BTreeSet<LocalName> = attrs.into_iter().map(LocalName::from).collect();
+        let mut allowed_attributes = BTreeMap::new();
+        for tag in &["use", "p", "svg"] {
+            allowed_attributes.insert(LocalName::from(*tag), allowed.clone());
+        }
+        SerializeOpts {
+            allowed_attributes: Some(allowed_attributes),
+            canonicalize_token_attrs: vec![local_name!("class")].into_iter().collect(),
+            lossless_namespaces: true,
+            pretty_print: true,
+            ..SerializeOpts::ugc()
+        }
+    };
+    let write = |opts: SerializeOpts| {
+        let dom = parse_fragment(
+            RcDom::default(),
+            ParseOpts::default(),
+            QualName::new(None, ns!(html), local_name!("body")),
+            vec![],
+        )
+        .one(input);
+        let mut output = vec![];
+        serialize(&mut output, &dom.document.children.borrow()[0], opts).unwrap();
+        output
+    };
+
+    let first = write(opts(false));
+    assert_eq!(write(opts(false)), first);
+    assert_eq!(write(opts(true)), first);
+}
+
+#[test]
+fn wrap_bare_text() {
+    let opts = SerializeOpts {
+        wrap_bare_text: true,
+        ..SerializeOpts::spec_compliant()
+    };
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<title>t</title>Hello <b>world</b><div>block <i>a</i></div>tail<p>para</p>\n");
+    assert_eq!(
+        serialize_to_string(&dom.document, opts.clone()),
+        "<html><head><title>t</title></head><body><p>Hello <b>world</b></p>\
+         <div>block <i>a</i></div><p>tail</p><p>para</p>\n</body></html>"
+    );
+
+    // The children of a `<body>` fragment are in the body context too.
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<ul><li>x</li></ul>a <em>b</em>");
+    let opts = SerializeOpts {
+        traversal_scope: TraversalScope::ChildrenOnly(Some(QualName::new(
+            None,
+            ns!(html),
+            local_name!("body"),
+        ))),
+        ..opts
+    };
+    let mut output = vec![];
+    serialize(&mut output, &dom.document.children.borrow()[0], opts).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "<ul><li>x</li></ul><p>a <em>b</em></p>"
+    );
+}
+
+#[test]
+fn bidi_controls() {
+    let dom = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("body")),
+        vec![],
+    )
+    .one("<p title=\"a\u{202E}b\">admin\u{2066}\u{202D}x\u{2069}\u{202C}</p>");
+    let write = |bidi_controls: BidiControls| {
+        let opts = SerializeOpts {
+            bidi_controls: bidi_controls,
+            ..SerializeOpts::spec_compliant()
+        };
+        serialize_to_string(&dom.document.children.borrow()[0], opts)
+    };
+    assert_eq!(
+        write(BidiControls::Keep),
+        "<p title=\"a\u{202E}b\">admin\u{2066}\u{202D}x\u{2069}\u{202C}</p>"
+    );
+    assert_eq!(
+        write(BidiControls::Escape),
+        "<p title=\"a&#x202e;b\">admin&#x2066;&#x202d;x&#x2069;&#x202c;</p>"
+    );
+    assert_eq!(write(BidiControls::Strip), "<p title=\"ab\">adminx</p>");
+}
+
+#[test]
+fn attribute_order_priority() {
+    let opts = SerializeOpts {
+        attribute_order_priority: vec![local_name!("id"), local_name!("class")],
+        ..SerializeOpts::spec_compliant()
+    };
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<a href="/" class="nav" title="t" id="home">x</a><p class=a>y</p>"#.to_tendril(),
+            opts.clone()
+        ),
+        r#"<a id="home" class="nav" href="/" title="t">x</a><p class="a">y</p>"#
+    );
+    assert_eq!(
+        &*parse_and_serialize_with(
+            r#"<div data-b="2" id="x" data-a="1"></div>"#.to_tendril(),
+            opts
+        ),
+        r#"<div id="x" data-b="2" data-a="1"></div>"#
+    );
+}
+
+#[test]
+fn ugc_responsive_images() {
+    let input = r#"<picture><source media="(min-width: 800px)" type="image/webp" srcset="javascript:alert(1) 1x, /large.webp 2x" onload="x()"><img src="/small.jpg" srcset="/a.jpg 480w, javascript:alert(2), /b.jpg 800w" sizes="(max-width: 600px) 480px, 800px" alt="a"></picture>"#;
+    assert_eq!(
+        sanitize_html(input, SerializeOpts::ugc()).unwrap(),
+        r#"<picture><source media="(min-width: 800px)" type="image/webp" srcset="/large.webp 2x"><img src="/small.jpg" srcset="/a.jpg 480w, /b.jpg 800w" sizes="(max-width: 600px) 480px, 800px" alt="a"></picture>"#
+    );
+
+    // Descriptors may contain commas in parentheses, and a srcset with only
+    // disallowed candidates is dropped.
+    let input = r#"<img srcset="data:x, JAVASCRIPT:y 2x" src="/a.png"><img srcset="/c.png 1x (a, b),/d.png">"#;
+    assert_eq!(
+        sanitize_html(input, SerializeOpts::ugc()).unwrap(),
+        r#"<img src="/a.png"><img srcset="/c.png 1x (a, b),/d.png">"#
+    );
+}
+
+// FIXME: test serialization of qualified tag/attribute names that can't be
+// parsed from HTML
+
+test!(attr_ns_1, r#"<svg xmlns="bleh"></svg>"#);
+test!(attr_ns_2, r#"<svg xmlns:foo="bleh"></svg>"#);
+test!(attr_ns_3, r#"<svg xmlns:xlink="bleh"></svg>"#);
+test!(attr_ns_4, r#"<svg xlink:href="bleh"></svg>"#);
+
+test_no_parse!(malformed_tokens, r#"foo</div><div>"#);
+
+fn repair_tree(input: StrTendril) -> StrTendril {
+    tokenize_and_serialize_with(
+        input,
+        SerializeOpts {
+            repair_tree: true,
+            ..Default::default()
+        },
+    )
+}
+
+test_fn!(
+    repair_tree,
+    repair_extra_end_tag,
+    "<p>a</p></em>b</p>",
+    "<p>a</p>b"
+);
+test_fn!(
+    repair_tree,
+    repair_missing_end_tag,
+    "<p><em>a",
+    "<p><em>a</em></p>"
+);
+test_fn!(
+    repair_tree,
+    repair_misnested_end_tag,
+    "<p><em>a</p>b",
+    "<p><em>a</em></p>b"
+);
+
+#[test]
+fn doctype() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default()).one("<!doctype html>");
+    dom.document.children.borrow_mut().truncate(1); // Remove <html>
+    let mut result = vec![];
+    serialize(&mut result, &dom.document, Default::default()).unwrap();
+    assert_eq!(String::from_utf8(result).unwrap(), "<!DOCTYPE html>");
+}
+
+#[test]
+fn deep_tree() {
+    let parser = parse_fragment(
+        RcDom::default(),
+        ParseOpts::default(),
+        QualName::new(None, ns!(html), local_name!("div")),
+        vec![],
+    );
+    let src = String::from("<b>".repeat(60_000));
+    let dom = parser.one(src);
+    let document = &dom.document;
+    let opts = SerializeOpts::default();
+    let mut ret_val = Vec::new();
+    serialize(&mut ret_val, document, opts)
+        .expect("Writing to a string shouldn't fail (expect on OOM)");
+}
+
+/// An `AsyncWrite` that takes at most 100 bytes at a time, and is only ready
+/// every other time it is polled.
+#[cfg(feature = "tokio")]
+struct SlowWriter {
+    written: Vec<u8>,
+    ready: bool,
+    /// The most output ever offered in one call.
+    max_offered: usize,
+}
+
+#[cfg(feature = "tokio")]
+impl tokio::io::AsyncWrite for SlowWriter {
+    fn poll_write(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context,
+        buf: &[u8],
+    ) -> std::task::Poll<io::Result<usize>> {
+        self.max_offered = self.max_offered.max(buf.len());
+        self.ready = !self.ready;
+        if !self.ready {
+            cx.waker().wake_by_ref();
+            return std::task::Poll::Pending;
+        }
+        let n = buf.len().min(100);
+        self.written.extend_from_slice(&buf[..n]);
+        std::task::Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        _: &mut std::task::Context,
+    ) -> std::task::Poll<io::Result<()>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[test]
+fn async_matches_sync() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<!doctype html><p>Hello <b>async</b> world</p><script>1 < 2</script>");
+    let mut expected = vec![];
+    serialize(&mut expected, &dom.document, Default::default()).unwrap();
+    let mut result = vec![];
+    runtime
+        .block_on(html5ever::serialize::serialize_async(
+            &mut result,
+            &dom.document,
+            Default::default(),
+        ))
+        .unwrap();
+    assert_eq!(result, expected);
+
+    // A document many times the size of the buffer is written a piece at a
+    // time, not serialized all at once.
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<ul>".to_string() + &"<li class=item>Hello <b>async</b> world</li>".repeat(5000));
+    let mut expected = vec![];
+    serialize(&mut expected, &dom.document, Default::default()).unwrap();
+    let mut writer = SlowWriter {
+        written: vec![],
+        ready: false,
+        max_offered: 0,
+    };
+    runtime
+        .block_on(html5ever::serialize::serialize_async(
+            &mut writer,
+            &dom.document,
+            Default::default(),
+        ))
+        .unwrap();
+    assert_eq!(writer.written, expected);
+    assert!(expected.len() > 100_000);
+    assert!(writer.max_offered < 10_000, "{}", writer.max_offered);
+}
+
+#[cfg(feature = "bytes")]
+#[test]
+fn bytes_matches_vec() {
+    let dom = parse_document(RcDom::default(), ParseOpts::default())
+        .one("<!doctype html><p>Hello <b>bytes</b> world</p><script>1 < 2</script>");
+
+    let mut expected = vec![];
+    serialize(&mut expected, &dom.document, Default::default()).unwrap();
+
+    let mut buf = bytes::BytesMut::with_capacity(16);
+    buf.extend_from_slice(b"HTTP body: ");
+    html5ever::serialize::serialize_into_bytes(&mut buf, &dom.document, Default::default())
+        .unwrap();
+    let body = buf.split_off(11).freeze();
+    assert_eq!(&buf[..], b"HTTP body: ");
+    assert_eq!(&body[..], &expected[..]);
 }
@@ -11,7 +11,7 @@
 use std::fmt;
 use tendril::StrTendril;
 
-pub use self::tree_builder::{create_element, AppendNode, AppendText, ElementFlags, NodeOrText};
+pub use self::tree_builder::{create_element, create_element_as_written, AppendNode, AppendText, ElementFlags, NodeOrText};
 pub use self::tree_builder::{LimitedQuirks, NoQuirks, Quirks, QuirksMode};
 pub use self::tree_builder::{NextParserState, Tracer, TreeSink};
 use super::{LocalName, Namespace, Prefix};
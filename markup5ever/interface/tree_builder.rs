@@ -71,6 +71,11 @@ pub struct ElementFlags {
     /// [whatwg integration-point]: https://html.spec.whatwg.org/multipage/#html-integration-point
     pub mathml_annotation_xml_integration_point: bool,
 
+    /// The element's start tag was self-closing, like `<br/>`. This has no
+    /// effect on parsing of HTML elements, but can be recorded so that the tag
+    /// is serialized the way it was written.
+    pub self_closing: bool,
+
     // Prevent construction from outside module
     _private: (),
 }
@@ -106,10 +111,25 @@ pub struct ElementFlags {
 ///
 /// ```
 pub fn create_element<Sink>(sink: &mut Sink, name: QualName, attrs: Vec<Attribute>) -> Sink::Handle
+where
+    Sink: TreeSink,
+{
+    create_element_as_written(sink, name, attrs, false)
+}
+
+/// Like `create_element`, for an element whose start tag was self-closing in
+/// the source if `self_closing` is set.
+pub fn create_element_as_written<Sink>(
+    sink: &mut Sink,
+    name: QualName,
+    attrs: Vec<Attribute>,
+    self_closing: bool,
+) -> Sink::Handle
 where
     Sink: TreeSink,
 {
     let mut flags = ElementFlags::default();
+    flags.self_closing = self_closing;
     match name.expanded() {
         expanded_name!(html "template") => flags.template = true,
         expanded_name!(mathml "annotation-xml") => {
@@ -111,6 +111,8 @@ pub struct Node {
     pub data: NodeData,
     /// Flag to control whether to free any children on destruction.
     leak_children_on_drop: Cell<bool>,
+    /// Whether the start tag was self-closing in the source, like `<br/>`.
+    self_closing: Cell<bool>,
 }
 
 impl Node {
@@ -121,9 +123,16 @@ impl Node {
             parent: Cell::new(None),
             children: RefCell::new(Vec::new()),
             leak_children_on_drop: Cell::new(true),
+            self_closing: Cell::new(false),
         })
     }
 
+    /// Whether this is an element whose start tag was self-closing in the
+    /// source, like `<br/>`.
+    pub fn is_self_closing(&self) -> bool {
+        self.self_closing.get()
+    }
+
     /// Drop any child nodes remaining in this node at destruction.
     ///
     /// RcDom's destructor automatically drops any nodes and children that are
@@ -288,7 +297,7 @@ impl TreeSink for RcDom {
         attrs: Vec<Attribute>,
         flags: ElementFlags,
     ) -> Handle {
-        Node::new(NodeData::Element {
+        let node = Node::new(NodeData::Element {
             name: name,
             attrs: RefCell::new(attrs),
             template_contents: if flags.template {
@@ -297,7 +306,9 @@ impl TreeSink for RcDom {
                 None
             },
             mathml_annotation_xml_integration_point: flags.mathml_annotation_xml_integration_point,
-        })
+        });
+        node.self_closing.set(flags.self_closing);
+        node
     }
 
     fn create_comment(&mut self, text: StrTendril) -> Handle {
@@ -467,66 +478,119 @@ enum SerializeOp {
     Close(QualName)
 }
 
+/// The children of a template element are in its [template contents], not
+/// under the element itself.
+///
+/// [template contents]: https://html.spec.whatwg.org/multipage/#template-contents
+fn template_contents(handle: &Handle) -> Option<&Handle> {
+    match handle.data {
+        NodeData::Element {
+            template_contents: Some(ref contents),
+            ..
+        } => Some(contents),
+        _ => None,
+    }
+}
+
+/// Serializes the document, after telling the serializer its quirks mode.
+impl Serialize for RcDom {
+    fn serialize<S>(&self, serializer: &mut S, traversal_scope: TraversalScope) -> io::Result<()>
+    where
+        S: Serializer,
+    {
+        serializer.set_quirks_mode(self.quirks_mode);
+        self.document.serialize(serializer, traversal_scope)
+    }
+}
+
 impl Serialize for Handle {
     fn serialize<S>(&self, serializer: &mut S, traversal_scope: TraversalScope) -> io::Result<()>
     where
         S: Serializer,
     {
-        let mut ops = match traversal_scope {
-            IncludeNode => vec![SerializeOp::Open(self.clone())],
-            ChildrenOnly(_) => self
+        let mut steps = SerializeSteps::new(self, traversal_scope);
+        while try!(steps.step(serializer)) {}
+        Ok(())
+    }
+}
+
+/// The traversal that `Handle` serializes with, one node or end tag at a time,
+/// so that a caller can stop between steps, e.g. to write out what has been
+/// serialized so far.
+pub struct SerializeSteps {
+    ops: Vec<SerializeOp>,
+}
+
+impl SerializeSteps {
+    /// Start a traversal of `handle` in `traversal_scope`.
+    pub fn new(handle: &Handle, traversal_scope: TraversalScope) -> SerializeSteps {
+        let ops = match traversal_scope {
+            IncludeNode => vec![SerializeOp::Open(handle.clone())],
+            ChildrenOnly(_) => template_contents(handle)
+                .unwrap_or(handle)
                 .children
                 .borrow()
                 .iter()
                 .map(|h| SerializeOp::Open(h.clone())).collect(),
         };
+        SerializeSteps { ops: ops }
+    }
 
-        while !ops.is_empty() {
-            match ops.remove(0) {
-                SerializeOp::Open(handle) => {
-                    match &handle.data {
-                        &NodeData::Element {
-                            ref name,
-                            ref attrs,
-                            ..
-                        } => {
-                            try!(serializer.start_elem(
-                                name.clone(),
-                                attrs.borrow().iter().map(|at| (&at.name, &at.value[..]))
-                            ));
-
-                            ops.insert(0, SerializeOp::Close(name.clone()));
-
-                            for child in handle.children.borrow().iter().rev() {
-                                ops.insert(0, SerializeOp::Open(child.clone()));
-                            }
+    /// Pass the next node or end tag to `serializer`. Returns `false` if the
+    /// traversal was already complete.
+    pub fn step<S>(&mut self, serializer: &mut S) -> io::Result<bool>
+    where
+        S: Serializer,
+    {
+        if self.ops.is_empty() {
+            return Ok(false);
+        }
+        match self.ops.remove(0) {
+            SerializeOp::Open(handle) => {
+                match &handle.data {
+                    &NodeData::Element {
+                        ref name,
+                        ref attrs,
+                        ..
+                    } => {
+                        try!(serializer.start_elem_as_written(
+                            name.clone(),
+                            attrs.borrow().iter().map(|at| (&at.name, &at.value[..])),
+                            handle.is_self_closing()
+                        ));
+
+                        self.ops.insert(0, SerializeOp::Close(name.clone()));
+
+                        let parent = template_contents(&handle).unwrap_or(&handle);
+                        for child in parent.children.borrow().iter().rev() {
+                            self.ops.insert(0, SerializeOp::Open(child.clone()));
                         }
+                    }
 
-                        &NodeData::Doctype { ref name, .. } => serializer.write_doctype(&name)?,
+                    &NodeData::Doctype { ref name, .. } => serializer.write_doctype(&name)?,
 
-                        &NodeData::Text { ref contents } => {
-                            serializer.write_text(&contents.borrow())?
-                        }
+                    &NodeData::Text { ref contents } => {
+                        serializer.write_text(&contents.borrow())?
+                    }
 
-                        &NodeData::Comment { ref contents } => {
-                            serializer.write_comment(&contents)?
-                        },
+                    &NodeData::Comment { ref contents } => {
+                        serializer.write_comment(&contents)?
+                    },
 
-                        &NodeData::ProcessingInstruction {
-                            ref target,
-                            ref contents,
-                        }  => serializer.write_processing_instruction(target, contents)?,
+                    &NodeData::ProcessingInstruction {
+                        ref target,
+                        ref contents,
+                    }  => serializer.write_processing_instruction(target, contents)?,
 
-                        &NodeData::Document => panic!("Can't serialize Document node itself"),
-                    }
+                    &NodeData::Document => panic!("Can't serialize Document node itself"),
                 }
+            }
 
-                SerializeOp::Close(name) => {
-                    try!(serializer.end_elem(name));
-                }
+            SerializeOp::Close(name) => {
+                try!(serializer.end_elem(name));
             }
         }
 
-        Ok(())
+        Ok(true)
     }
 }
@@ -13,12 +13,14 @@
 //!
 //! [processing instructions]: https://en.wikipedia.org/wiki/Processing_Instruction
 
+use interface::QuirksMode;
 use std::io;
+use std::ops::Range;
 use QualName;
 
 //§ serializing-html-fragments
 /// Used as a parameter to `serialize`, telling it if we want to skip the parent.
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum TraversalScope {
     /// Include the parent node when serializing.
     IncludeNode,
@@ -48,21 +50,64 @@ pub trait Serializer {
     where
         AttrIter: Iterator<Item = AttrRef<'a>>;
 
+    /// Serialize the start of an element whose start tag was self-closing in
+    /// the source, like `<br/>`, if `self_closing` is set, so that a serializer
+    /// can write it the same way. By default this is passed on to `start_elem`.
+    fn start_elem_as_written<'a, AttrIter>(
+        &mut self,
+        name: QualName,
+        attrs: AttrIter,
+        self_closing: bool,
+    ) -> io::Result<()>
+    where
+        AttrIter: Iterator<Item = AttrRef<'a>>,
+    {
+        let _ = self_closing;
+        self.start_elem(name, attrs)
+    }
+
     /// Serialize the end of an element, for example `</div>`.
     fn end_elem(&mut self, name: QualName) -> io::Result<()>;
 
     /// Serialize a plain text node.
     fn write_text(&mut self, text: &str) -> io::Result<()>;
 
+    /// Serialize a plain text node that came from source containing character
+    /// references, so that a serializer can write them the way they were
+    /// written. `entities` are sorted and don't overlap. By default they are
+    /// ignored and the text is passed to `write_text`.
+    ///
+    /// The tokenizer, the tree builder and `RcDom` don't record references, so
+    /// this is only called by `Serialize` implementations for trees that keep
+    /// them some other way, such as ones built by hand or by another parser.
+    fn write_text_with_entities(&mut self, text: &str, entities: &[EntitySpan]) -> io::Result<()> {
+        let _ = entities;
+        self.write_text(text)
+    }
+
     /// Serialize a comment node, for example `<!-- comment -->`.
     fn write_comment(&mut self, text: &str) -> io::Result<()>;
 
     /// Serialize a doctype node, for example `<!doctype html>`.
     fn write_doctype(&mut self, name: &str) -> io::Result<()>;
 
+    /// Serialize a doctype node given as everything between `<!DOCTYPE ` and `>`,
+    /// for example `html PUBLIC "-//W3C//DTD HTML 4.01//EN"`, to be written
+    /// exactly as it is. By default it is passed to `write_doctype` as the name.
+    fn write_doctype_raw(&mut self, raw: &str) -> io::Result<()> {
+        self.write_doctype(raw)
+    }
+
     /// Serialize a processing instruction node, for example
     /// `<?xml-stylesheet type="text/xsl" href="style.xsl"?>`.
     fn write_processing_instruction(&mut self, target: &str, data: &str) -> io::Result<()>;
+
+    /// Called before anything else with the quirks mode of the document being
+    /// serialized, by implementations of `Serialize` that know it. By default it
+    /// is ignored.
+    fn set_quirks_mode(&mut self, mode: QuirksMode) {
+        let _ = mode;
+    }
 }
 
 /// A type alias for an attribute name and value (e.g. the `class="test"` in `<div class="test">`
@@ -73,3 +118,16 @@ pub trait Serializer {
 ///
 /// [`Serializer::start_elem`]: trait.Serializer.html#tymethod.start_elem
 pub type AttrRef<'a> = (&'a QualName, &'a str);
+
+/// A character reference in the source of a text node. Nothing in this crate
+/// or html5ever creates these while parsing; see
+/// [`Serializer::write_text_with_entities`].
+///
+/// [`Serializer::write_text_with_entities`]: trait.Serializer.html#method.write_text_with_entities
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntitySpan<'a> {
+    /// The bytes of the text that the reference stands for.
+    pub range: Range<usize>,
+    /// The reference as it was written, for example `&copy;`.
+    pub source: &'a str,
+}